@@ -1,5 +1,34 @@
+use ethers_contract_abigen::Abigen;
+use ethers_core::abi::{Abi, Function, FunctionExt, ParamType, StateMutability};
+use inflector::Inflector;
+use std::collections::HashMap;
+use std::path::Path;
 use std::process::Command;
 
+struct Bindings {
+    name: &'static str,
+    abi: &'static str,
+    /// `(signature, method name)`. overloaded functions need one or abigen gives them numbered names
+    aliases: &'static [(&'static str, &'static str)],
+}
+
+/// The contracts that get rust bindings.
+const CONTRACTS: [Bindings; 2] = [
+    Bindings {
+        name: "AldersonDiceGameV0",
+        abi: "src-js/AldersonDiceGameV0.json",
+        aliases: &[
+            ("currentBag()", "current_bag"),
+            ("currentBag((uint256),uint256)", "current_bag_with_prng"),
+        ],
+    },
+    Bindings {
+        name: "AldersonDiceNFT",
+        abi: "src-js/AldersonDiceNFT.json",
+        aliases: &[],
+    },
+];
+
 fn main() {
     // Run the webpack build command
     let status = Command::new("node")
@@ -12,8 +41,244 @@ fn main() {
         panic!("esbuild failed");
     }
 
+    let out_dir = std::env::var("OUT_DIR").expect("no OUT_DIR");
+
+    for x in CONTRACTS {
+        write_bindings(x.name, x.abi, x.aliases, Path::new(&out_dir));
+    }
+
     println!("cargo:rerun-if-changed=src-js/");
     println!("cargo:rerun-if-changed=esbuild.js");
     println!("cargo:rerun-if-changed=tsconfig.json");
     println!("cargo:rerun-if-changed=yarn.lock");
 }
+
+/// Abigen's types plus a `{name}Reader` that does typed `eth_call`s through our own transports.
+fn write_bindings(name: &str, path: &str, aliases: &[(&str, &str)], out_dir: &Path) {
+    // serde so that leptos resources can hold them
+    let mut abigen = Abigen::new(name, path)
+        .and_then(|x| x.add_derive("serde::Serialize"))
+        .and_then(|x| x.add_derive("serde::Deserialize"))
+        .expect("failed to load abi");
+
+    for (signature, alias) in aliases {
+        abigen = abigen.add_method_alias(*signature, *alias);
+    }
+
+    let mut code = abigen
+        .generate()
+        .expect("failed to generate bindings")
+        .to_string();
+
+    let artifact: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(path).expect("failed to read abi"))
+            .expect("abi is not json");
+
+    // forge artifacts have the abi under a key. plain abi files are just the array
+    let abi = artifact.get("abi").cloned().unwrap_or(artifact);
+
+    let abi: Abi = serde_json::from_value(abi).expect("invalid abi");
+
+    code.push_str(&reader(name, &abi, aliases));
+
+    let file = out_dir.join(format!("{}.rs", name.to_snake_case()));
+
+    std::fs::write(file, code).expect("failed to write bindings");
+}
+
+fn reader(name: &str, abi: &Abi, aliases: &[(&str, &str)]) -> String {
+    let aliases: HashMap<&str, &str> = aliases.iter().copied().collect();
+
+    let mut methods = String::new();
+
+    for (fn_name, overloads) in &abi.functions {
+        for function in overloads {
+            // abigen names the structs after the alias if there is one
+            let (method, struct_name) = match aliases.get(function.abi_signature().as_str()) {
+                Some(x) => (x.to_string(), x.to_pascal_case()),
+                None if overloads.len() == 1 => (fn_name.to_snake_case(), fn_name.to_pascal_case()),
+                // abigen's numbered names for overloads aren't worth guessing
+                None => continue,
+            };
+
+            let read_only = matches!(
+                function.state_mutability,
+                StateMutability::View | StateMutability::Pure
+            );
+
+            if !read_only || function.outputs.is_empty() {
+                continue;
+            }
+
+            match reader_method(&method, &struct_name, function) {
+                Some(x) => methods.push_str(&x),
+                None => println!(
+                    "cargo:warning={} has no typed reader for {}",
+                    name,
+                    function.signature()
+                ),
+            }
+        }
+    }
+
+    format!(
+        r#"
+/// Typed reads of `{name}` at one block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct {name}Reader<T> {{
+    client: crate::public_client::PublicClient<T>,
+    address: ::ethers::core::types::Address,
+    block: ::ethers::core::types::BlockId,
+}}
+
+impl<T: crate::transport::Transport> {name}Reader<T> {{
+    /// Reads from the latest block until [`Self::at`] says otherwise.
+    pub fn new(
+        client: crate::public_client::PublicClient<T>,
+        address: ::ethers::core::types::Address,
+    ) -> Self {{
+        Self {{
+            client,
+            address,
+            block: ::ethers::core::types::BlockNumber::Latest.into(),
+        }}
+    }}
+
+    /// Read at `block` instead.
+    pub fn at(mut self, block: impl Into<::ethers::core::types::BlockId>) -> Self {{
+        self.block = block.into();
+        self
+    }}
+
+    pub fn address(&self) -> ::ethers::core::types::Address {{
+        self.address
+    }}
+
+    pub fn block(&self) -> ::ethers::core::types::BlockId {{
+        self.block
+    }}
+
+    async fn read<R: ::ethers::core::abi::AbiDecode>(
+        &self,
+        selector: ::ethers::core::types::Selector,
+        args: &[::ethers::core::abi::Token],
+    ) -> Result<R, crate::transport::TransportError> {{
+        let mut data = selector.to_vec();
+        data.extend(::ethers::core::abi::encode(args));
+
+        let tx = ::ethers::core::types::TransactionRequest::new()
+            .to(self.address)
+            .data(data);
+
+        let x = self.client.call(&tx, self.block).await?;
+
+        R::decode(x).map_err(|err| crate::transport::TransportError::InvalidResponse(err.to_string()))
+    }}
+{methods}}}
+"#
+    )
+}
+
+fn reader_method(method: &str, struct_name: &str, function: &Function) -> Option<String> {
+    let call = format!("{}Call", struct_name);
+
+    let mut args = vec![];
+    let mut tokens = vec![];
+
+    for (i, input) in function.inputs.iter().enumerate() {
+        let arg = if input.name.is_empty() {
+            format!("p{}", i)
+        } else {
+            safe_ident(input.name.to_snake_case())
+        };
+
+        let ty = rust_type(&input.kind, input.internal_type.as_deref())?;
+
+        args.push(format!("{}: {}", arg, ty));
+        tokens.push(format!(
+            "::ethers::core::abi::Tokenizable::into_token({})",
+            arg
+        ));
+    }
+
+    let (ret, decode) = if let [output] = function.outputs.as_slice() {
+        let ty = rust_type(&output.kind, output.internal_type.as_deref())?;
+
+        (ty.clone(), format!("let (x,): ({},) = ", ty))
+    } else {
+        let ret = format!("{}Return", struct_name);
+
+        (ret.clone(), format!("let x: {} = ", ret))
+    };
+
+    Some(format!(
+        r#"
+    /// `{signature}`
+    pub async fn {method}(&self, {args}) -> Result<{ret}, crate::transport::TransportError> {{
+        {decode}self
+            .read(
+                <{call} as ::ethers::contract::EthCall>::selector(),
+                &[{tokens}],
+            )
+            .await?;
+
+        Ok(x)
+    }}
+"#,
+        signature = function.signature(),
+        args = args.join(", "),
+        tokens = tokens.join(", "),
+    ))
+}
+
+/// The same rust types that abigen uses. None for types we don't need yet.
+fn rust_type(kind: &ParamType, internal_type: Option<&str>) -> Option<String> {
+    let x = match kind {
+        ParamType::Address => "::ethers::core::types::Address".to_string(),
+        ParamType::Bool => "bool".to_string(),
+        ParamType::String => "::std::string::String".to_string(),
+        ParamType::Bytes => "::ethers::core::types::Bytes".to_string(),
+        ParamType::FixedBytes(n) => format!("[u8; {}]", n),
+        ParamType::Uint(n) => match n / 8 {
+            1 => "u8".to_string(),
+            2 => "u16".to_string(),
+            3..=4 => "u32".to_string(),
+            5..=8 => "u64".to_string(),
+            9..=16 => "u128".to_string(),
+            _ => "::ethers::core::types::U256".to_string(),
+        },
+        ParamType::Int(n) => match n / 8 {
+            1 => "i8".to_string(),
+            2 => "i16".to_string(),
+            3..=4 => "i32".to_string(),
+            5..=8 => "i64".to_string(),
+            9..=16 => "i128".to_string(),
+            _ => "::ethers::core::types::I256".to_string(),
+        },
+        // abigen uses a wrapper type for these
+        ParamType::FixedArray(x, _) if matches!(**x, ParamType::Uint(8)) => return None,
+        ParamType::FixedArray(x, n) => format!("[{}; {}]", rust_type(x, internal_type)?, n),
+        ParamType::Array(x) => format!("::std::vec::Vec<{}>", rust_type(x, internal_type)?),
+        // "struct AldersonDiceGameV0.DieInfo[5]" is `DieInfo`
+        ParamType::Tuple(_) => {
+            let x = internal_type?.strip_prefix("struct ")?;
+            let x = x.rsplit('.').next()?;
+            let x = x.split('[').next()?;
+
+            x.to_pascal_case()
+        }
+    };
+
+    Some(x)
+}
+
+fn safe_ident(x: String) -> String {
+    match x.as_str() {
+        "as" | "async" | "await" | "box" | "break" | "const" | "continue" | "crate" | "dyn"
+        | "else" | "enum" | "extern" | "false" | "fn" | "for" | "if" | "impl" | "in" | "let"
+        | "loop" | "match" | "mod" | "move" | "mut" | "pub" | "ref" | "return" | "self"
+        | "static" | "struct" | "super" | "trait" | "true" | "type" | "unsafe" | "use"
+        | "where" | "while" => format!("{}_", x),
+        _ => x,
+    }
+}
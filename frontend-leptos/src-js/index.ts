@@ -1,9 +1,6 @@
-import { Chain, http, custom, createPublicClient, createWalletClient, fallback, getContract } from 'viem'
+import { Chain, custom, createPublicClient, createWalletClient, fallback } from 'viem'
 import { arbitrum, base, localhost, mainnet } from 'viem/chains'
 
-import { abi as gameAbi } from "./AldersonDiceGameV0.json";
-import { abi as nftAbi } from "./AldersonDiceNFT.json";
-
 export function hello() {
   return 'Hello, world!';
 }
@@ -23,17 +20,17 @@ function chainIdToChain(chainId): Chain {
   }
 }
 
-export function createPublicClientForChain(chainId, eip1193_provider) {
-  // TODO: allow customizing this. dev we want 8545. prod we want undefined
-  const fallbackUrl = "http://127.0.0.1:8545";
+// rpcProvider is the app's own fallback transport over the configured rpcs. it ranks them, so viem doesn't have to
+export function createPublicClientForChain(chainId, eip1193_provider, rpcProvider) {
+  const rpc = custom(rpcProvider);
 
   let transport;
   if (eip1193_provider === undefined) {
-    transport = http(fallbackUrl);
+    transport = rpc;
   } else {
     transport = fallback([
       custom(eip1193_provider),
-      http(fallbackUrl),
+      rpc,
     ]);
   }
 
@@ -56,41 +53,3 @@ export function createWalletClientForChain(chainId, eip1193Provider) {
 
   return walletClient;
 };
-
-export function nftContract(publicClient, walletClient, address) {
-  let client;
-
-  if (walletClient === undefined) {
-    client = publicClient;
-  } else {
-    client = {
-      public: publicClient,
-      wallet: walletClient,
-    };
-  }
-
-  return getContract({
-    address,
-    abi: nftAbi,
-    client,
-  });
-}
-
-export function gameContract(publicClient, walletClient, address) {
-  let client;
-
-  if (walletClient === undefined) {
-    client = publicClient;
-  } else {
-    client = {
-      public: publicClient,
-      wallet: walletClient,
-    };
-  }
-
-  return getContract({
-    address,
-    abi: gameAbi,
-    client,
-  });
-}
@@ -0,0 +1,640 @@
+//! Every matching log from a starting block up to the chain's head, and then new ones as blocks arrive.
+//!
+//! Public RPCs limit how many blocks one `eth_getLogs` can cover and they don't agree on the limit. Chunks are halved
+//! whenever the node says a range is too large and grow back after they succeed.
+//!
+//! The hashes of recently backfilled blocks are kept in the [`Checkpoint`]. If one of them changes, a reorg replaced
+//! those blocks and everything after the last block that still matches is fetched again.
+use crate::events::EventLog;
+use crate::public_client::PublicClient;
+use crate::transport::{Transport, TransportError};
+use ethers::types::{Address, Filter, H256};
+use futures::Stream;
+use gloo_timers::future::TimeoutFuture;
+use leptos::logging::warn;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// blocks per `eth_getLogs` until a node complains
+pub const DEFAULT_CHUNK_SIZE: u64 = 10_000;
+
+/// how many block hashes to remember for reorg checks. reorgs deeper than this start over from the beginning
+const MAX_HASHES: usize = 32;
+
+/// Nodes don't agree on how to say that a range is too big. These are from the public RPCs that we use.
+const RANGE_TOO_LARGE: [&str; 8] = [
+    "block range",
+    "range is too",
+    "range too",
+    "ranges over",
+    "more than",
+    "too large",
+    "too wide",
+    "response size",
+];
+
+/// True if `err` means that `eth_getLogs` should be retried with fewer blocks.
+pub fn is_range_too_large(err: &TransportError) -> bool {
+    if matches!(err, TransportError::Http(_)) {
+        return false;
+    }
+
+    // limit exceeded
+    if err.code() == Some(-32005) {
+        return true;
+    }
+
+    let message = err.to_string().to_lowercase();
+
+    RANGE_TOO_LARGE.iter().any(|x| message.contains(x))
+}
+
+/// How far a backfill has gotten. Save it after handling each [`BackfillStep`] to resume from there later.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// where the backfill started. usually the block that the contracts were deployed in
+    pub start_block: u64,
+    /// every block before this has been backfilled
+    pub next_block: u64,
+    /// `(number, hash)` of the last block of recent chunks. oldest first
+    pub hashes: Vec<(u64, H256)>,
+}
+
+impl Checkpoint {
+    pub fn new(start_block: u64) -> Self {
+        Self {
+            start_block,
+            next_block: start_block,
+            hashes: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BackfillStep {
+    /// the logs in `from_block..=to_block`
+    Logs {
+        from_block: u64,
+        to_block: u64,
+        logs: Vec<EventLog>,
+    },
+    /// a reorg replaced blocks. forget anything from `from_block` on. those blocks are fetched again
+    Reorg { from_block: u64 },
+    /// nothing new until the next block
+    CaughtUp,
+}
+
+pub struct Backfill<T> {
+    client: PublicClient<T>,
+    game: Address,
+    nft: Address,
+    filter: Filter,
+    checkpoint: Checkpoint,
+    chunk_size: u64,
+    max_chunk_size: u64,
+}
+
+impl<T: Transport> Backfill<T> {
+    /// `filter`'s block range is ignored. The range comes from `checkpoint`.
+    ///
+    /// Logs that aren't from `game` or `nft` are skipped even if `filter` matches them.
+    pub fn new(
+        client: PublicClient<T>,
+        game: Address,
+        nft: Address,
+        filter: Filter,
+        checkpoint: Checkpoint,
+    ) -> Self {
+        Self {
+            client,
+            game,
+            nft,
+            filter,
+            checkpoint,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            max_chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// The most blocks to ask for at once. Chunks are smaller while a node is rejecting this many.
+    pub fn chunk_size(mut self, x: u64) -> Self {
+        self.chunk_size = x.max(1);
+        self.max_chunk_size = self.chunk_size;
+        self
+    }
+
+    pub fn checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+
+    async fn block_hash(&self, number: u64) -> Result<Option<H256>, TransportError> {
+        let x = self.client.get_block_header(number.into()).await?;
+
+        Ok(x.map(|x| x.hash))
+    }
+
+    /// Rewind the checkpoint to the newest remembered block that is still on the chain.
+    ///
+    /// A node that doesn't have one of the blocks yet is behind, not reorged. Nothing is rewound for that.
+    async fn check_reorg(&mut self) -> Result<Option<BackfillStep>, TransportError> {
+        let Some(&(number, hash)) = self.checkpoint.hashes.last() else {
+            return Ok(None);
+        };
+
+        match self.block_hash(number).await? {
+            Some(x) if x == hash => return Ok(None),
+            Some(_) => {}
+            // a load balancer sent us to a node that is behind. try again later
+            None => return Ok(Some(BackfillStep::CaughtUp)),
+        }
+
+        // the checkpoint isn't changed until we know where the chains meet
+        for keep in (1..self.checkpoint.hashes.len()).rev() {
+            let (number, hash) = self.checkpoint.hashes[keep - 1];
+
+            match self.block_hash(number).await? {
+                Some(x) if x == hash => {
+                    self.checkpoint.hashes.truncate(keep);
+                    self.checkpoint.next_block = number + 1;
+
+                    return Ok(Some(BackfillStep::Reorg {
+                        from_block: number + 1,
+                    }));
+                }
+                Some(_) => {}
+                None => return Ok(Some(BackfillStep::CaughtUp)),
+            }
+        }
+
+        // deeper than we remember
+        self.checkpoint.hashes.clear();
+        self.checkpoint.next_block = self.checkpoint.start_block;
+
+        Ok(Some(BackfillStep::Reorg {
+            from_block: self.checkpoint.start_block,
+        }))
+    }
+
+    /// Check for a reorg and then get the next chunk of logs.
+    pub async fn step(&mut self) -> Result<BackfillStep, TransportError> {
+        if let Some(x) = self.check_reorg().await? {
+            return Ok(x);
+        }
+
+        let head = self.client.block_number().await?;
+
+        let from_block = self.checkpoint.next_block;
+
+        if from_block > head {
+            return Ok(BackfillStep::CaughtUp);
+        }
+
+        let (to_block, hash, logs) = loop {
+            let to_block = from_block.saturating_add(self.chunk_size - 1).min(head);
+
+            let Some(hash) = self.block_hash(to_block).await? else {
+                // a load balancer sent us to a node that is behind. try again later
+                return Ok(BackfillStep::CaughtUp);
+            };
+
+            let filter = self
+                .filter
+                .clone()
+                .from_block(from_block)
+                .to_block(to_block);
+
+            match self.client.get_logs(&filter).await {
+                Ok(x) => {
+                    self.chunk_size = self.chunk_size.saturating_mul(2).min(self.max_chunk_size);
+                    break (to_block, hash, x);
+                }
+                Err(err) if self.chunk_size > 1 && is_range_too_large(&err) => {
+                    self.chunk_size /= 2;
+                }
+                Err(err) => return Err(err),
+            }
+        };
+
+        // a reorg while the logs were being fetched would leave them on a different chain than `hash`. the checkpoint
+        // can't move past them then
+        let same_chain = logs
+            .iter()
+            .filter(|x| x.block_number == Some(to_block.into()))
+            .all(|x| x.block_hash == Some(hash));
+
+        if !same_chain || self.block_hash(to_block).await? != Some(hash) {
+            warn!(
+                "block {} changed while getting its logs. trying again",
+                to_block
+            );
+            return Ok(BackfillStep::CaughtUp);
+        }
+
+        let logs = logs
+            .iter()
+            .filter_map(|x| match EventLog::decode(x, self.game, self.nft) {
+                Ok(x) => Some(x),
+                Err(err) => {
+                    warn!("skipping log: {}", err);
+                    None
+                }
+            })
+            .collect();
+
+        self.checkpoint.next_block = to_block + 1;
+        self.checkpoint.hashes.push((to_block, hash));
+
+        if self.checkpoint.hashes.len() > MAX_HASHES {
+            self.checkpoint.hashes.remove(0);
+        }
+
+        Ok(BackfillStep::Logs {
+            from_block,
+            to_block,
+            logs,
+        })
+    }
+
+    /// Backfill and then keep following the chain, checking for new blocks every `interval`.
+    ///
+    /// Each item comes with the checkpoint to save after handling it. Errors are retried after `interval`. Dropping
+    /// the stream stops it.
+    pub fn live(
+        self,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<(BackfillStep, Checkpoint), TransportError>> {
+        futures::stream::unfold(self, move |mut backfill| async move {
+            loop {
+                match backfill.step().await {
+                    Ok(BackfillStep::CaughtUp) => {}
+                    Ok(x) => {
+                        let checkpoint = backfill.checkpoint.clone();
+                        return Some((Ok((x, checkpoint)), backfill));
+                    }
+                    Err(err) => {
+                        TimeoutFuture::new(interval.as_millis() as u32).await;
+                        return Some((Err(err), backfill));
+                    }
+                }
+
+                TimeoutFuture::new(interval.as_millis() as u32).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::game::SetMintDevFeeFilter;
+    use crate::events::DiceEvent;
+    use ethers::abi::AbiEncode;
+    use ethers::contract::EthEvent;
+    use ethers::types::{Address, Log, U256, U64};
+    use futures::executor::block_on;
+    use serde::{de::DeserializeOwned, Serialize};
+    use serde_json::{json, Value};
+    use std::cell::Cell;
+
+    const GAME: Address = Address::repeat_byte(0x22);
+    const NFT: Address = Address::repeat_byte(0x33);
+
+    /// A chain with a `SetMintDevFee` log in some blocks. Blocks from `fork_block` on have different hashes.
+    ///
+    /// `fork_while_getting_logs` moves `fork_block` during the next `eth_getLogs`.
+    struct FakeChain {
+        head: u64,
+        max_range: u64,
+        log_blocks: Vec<u64>,
+        fork_block: Cell<u64>,
+        fork_while_getting_logs: Cell<Option<u64>>,
+        /// a block that the node doesn't have, like on a node that is behind
+        missing: Cell<Option<u64>>,
+        get_logs: Cell<usize>,
+    }
+
+    impl FakeChain {
+        fn hash(&self, number: u64) -> H256 {
+            let fork = if number >= self.fork_block.get() {
+                1 << 32
+            } else {
+                0
+            };
+
+            H256::from_low_u64_be(number + fork)
+        }
+
+        fn log(&self, number: u64) -> Log {
+            Log {
+                address: GAME,
+                topics: vec![SetMintDevFeeFilter::signature()],
+                data: U256::from(number).encode().into(),
+                block_number: Some(number.into()),
+                block_hash: Some(self.hash(number)),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl Transport for FakeChain {
+        async fn request<P: Serialize + ?Sized, R: DeserializeOwned>(
+            &self,
+            method: &str,
+            params: &P,
+        ) -> Result<R, TransportError> {
+            let params = serde_json::to_value(params).unwrap();
+
+            let block = |x: &Value| serde_json::from_value::<U64>(x.clone()).unwrap().as_u64();
+
+            let result = match method {
+                "eth_blockNumber" => json!(U64::from(self.head)),
+                "eth_getBlockByNumber" => {
+                    let number = block(&params[0]);
+
+                    if self.missing.get() == Some(number) {
+                        return Ok(serde_json::from_value(Value::Null).unwrap());
+                    }
+
+                    json!({
+                        "number": U64::from(number),
+                        "hash": self.hash(number),
+                        "parentHash": self.hash(number.saturating_sub(1)),
+                        "timestamp": "0x0",
+                        "miner": Address::zero(),
+                        "gasLimit": "0x0",
+                        "gasUsed": "0x0",
+                    })
+                }
+                "eth_getLogs" => {
+                    self.get_logs.set(self.get_logs.get() + 1);
+
+                    if let Some(x) = self.fork_while_getting_logs.take() {
+                        self.fork_block.set(x);
+                    }
+
+                    let from = block(&params[0]["fromBlock"]);
+                    let to = block(&params[0]["toBlock"]);
+
+                    if to - from + 1 > self.max_range {
+                        return Err(TransportError::Rpc {
+                            code: -32000,
+                            message: format!("block range is too wide. max is {}", self.max_range),
+                            data: None,
+                        });
+                    }
+
+                    let logs = self
+                        .log_blocks
+                        .iter()
+                        .filter(|&&x| (from..=to).contains(&x))
+                        .map(|&x| self.log(x))
+                        .collect::<Vec<_>>();
+
+                    json!(logs)
+                }
+                _ => panic!("unexpected {}", method),
+            };
+
+            Ok(serde_json::from_value(result).unwrap())
+        }
+    }
+
+    fn chain(head: u64, max_range: u64) -> FakeChain {
+        FakeChain {
+            head,
+            max_range,
+            log_blocks: vec![5, 50, 99],
+            fork_block: Cell::new(u64::MAX),
+            fork_while_getting_logs: Cell::new(None),
+            missing: Cell::new(None),
+            get_logs: Cell::new(0),
+        }
+    }
+
+    fn fees(step: &BackfillStep) -> Vec<u64> {
+        let BackfillStep::Logs { logs, .. } = step else {
+            return vec![];
+        };
+
+        logs.iter()
+            .map(|x| match &x.event {
+                DiceEvent::SetMintDevFee(x) => x.new_fee.as_u64(),
+                x => panic!("unexpected {:?}", x),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn splits_ranges_that_are_too_large() {
+        let mut backfill = Backfill::new(
+            PublicClient::new(chain(99, 30)),
+            GAME,
+            NFT,
+            Filter::new(),
+            Checkpoint::new(0),
+        );
+
+        let mut found = vec![];
+
+        loop {
+            let step = block_on(backfill.step()).unwrap();
+
+            if step == BackfillStep::CaughtUp {
+                break;
+            }
+
+            if let BackfillStep::Logs {
+                from_block,
+                to_block,
+                ..
+            } = step
+            {
+                assert!(to_block - from_block < 30);
+            }
+
+            found.extend(fees(&step));
+        }
+
+        assert_eq!(found, vec![5, 50, 99]);
+        assert_eq!(backfill.checkpoint().next_block, 100);
+    }
+
+    #[test]
+    fn rewinds_after_a_reorg() {
+        let mut backfill = Backfill::new(
+            PublicClient::new(chain(99, 1_000)),
+            GAME,
+            NFT,
+            Filter::new(),
+            Checkpoint::new(0),
+        )
+        .chunk_size(25);
+
+        while block_on(backfill.step()).unwrap() != BackfillStep::CaughtUp {}
+
+        let ends = backfill
+            .checkpoint()
+            .hashes
+            .iter()
+            .map(|x| x.0)
+            .collect::<Vec<_>>();
+
+        assert_eq!(ends, vec![24, 49, 74, 99]);
+
+        backfill.client.transport().fork_block.set(60);
+
+        assert_eq!(
+            block_on(backfill.step()).unwrap(),
+            BackfillStep::Reorg { from_block: 50 }
+        );
+
+        // the replaced blocks are fetched again
+        let step = block_on(backfill.step()).unwrap();
+
+        assert!(matches!(
+            step,
+            BackfillStep::Logs {
+                from_block: 50,
+                to_block: 74,
+                ..
+            }
+        ));
+        assert_eq!(fees(&step), vec![50]);
+    }
+
+    #[test]
+    fn resumes_from_a_checkpoint() {
+        let fake = chain(99, 1_000);
+
+        let checkpoint = Checkpoint {
+            start_block: 0,
+            next_block: 51,
+            hashes: vec![(50, fake.hash(50))],
+        };
+
+        let mut backfill = Backfill::new(
+            PublicClient::new(fake),
+            GAME,
+            NFT,
+            Filter::new(),
+            checkpoint,
+        );
+
+        let step = block_on(backfill.step()).unwrap();
+
+        assert_eq!(fees(&step), vec![99]);
+        assert_eq!(backfill.client.transport().get_logs.get(), 1);
+    }
+
+    #[test]
+    fn retries_chunks_that_reorg_while_getting_logs() {
+        let fake = chain(99, 1_000);
+
+        fake.fork_while_getting_logs.set(Some(90));
+
+        let mut backfill = Backfill::new(
+            PublicClient::new(fake),
+            GAME,
+            NFT,
+            Filter::new(),
+            Checkpoint::new(0),
+        )
+        .chunk_size(100);
+
+        assert_eq!(block_on(backfill.step()).unwrap(), BackfillStep::CaughtUp);
+        assert_eq!(backfill.checkpoint(), &Checkpoint::new(0));
+
+        let step = block_on(backfill.step()).unwrap();
+
+        assert_eq!(fees(&step), vec![5, 50, 99]);
+        assert_eq!(
+            backfill.checkpoint().hashes,
+            vec![(99, backfill.client.transport().hash(99))]
+        );
+    }
+
+    #[test]
+    fn waits_for_nodes_that_are_behind() {
+        let mut backfill = Backfill::new(
+            PublicClient::new(chain(99, 1_000)),
+            GAME,
+            NFT,
+            Filter::new(),
+            Checkpoint::new(0),
+        )
+        .chunk_size(25);
+
+        while block_on(backfill.step()).unwrap() != BackfillStep::CaughtUp {}
+
+        let checkpoint = backfill.checkpoint().clone();
+
+        // a lagging node doesn't have the last checkpointed block yet
+        backfill.client.transport().missing.set(Some(99));
+
+        assert_eq!(block_on(backfill.step()).unwrap(), BackfillStep::CaughtUp);
+        assert_eq!(backfill.checkpoint(), &checkpoint);
+
+        // looking for where a reorg started waits for missing blocks too
+        backfill.client.transport().fork_block.set(60);
+        backfill.client.transport().missing.set(Some(49));
+
+        assert_eq!(block_on(backfill.step()).unwrap(), BackfillStep::CaughtUp);
+        assert_eq!(backfill.checkpoint().next_block, 100);
+        assert_eq!(backfill.checkpoint().hashes.len(), checkpoint.hashes.len());
+
+        backfill.client.transport().missing.set(None);
+
+        assert_eq!(
+            block_on(backfill.step()).unwrap(),
+            BackfillStep::Reorg { from_block: 50 }
+        );
+    }
+
+    #[test]
+    fn huge_chunks_do_not_overflow() {
+        let fake = chain(99, 1_000);
+
+        let checkpoint = Checkpoint {
+            start_block: 0,
+            next_block: 51,
+            hashes: vec![(50, fake.hash(50))],
+        };
+
+        let mut backfill = Backfill::new(
+            PublicClient::new(fake),
+            GAME,
+            NFT,
+            Filter::new(),
+            checkpoint,
+        )
+        .chunk_size(u64::MAX);
+
+        let step = block_on(backfill.step()).unwrap();
+
+        assert_eq!(fees(&step), vec![99]);
+        assert_eq!(backfill.checkpoint().next_block, 100);
+    }
+
+    #[test]
+    fn recognizes_range_errors() {
+        let rpc = |code, message: &str| TransportError::Rpc {
+            code,
+            message: message.to_string(),
+            data: None,
+        };
+
+        assert!(is_range_too_large(&rpc(
+            -32005,
+            "query returned more than 10000 results"
+        )));
+        assert!(is_range_too_large(&rpc(
+            -32600,
+            "ranges over 10000 blocks are not supported on freetier"
+        )));
+        assert!(is_range_too_large(&rpc(
+            -32000,
+            "exceed maximum block range: 50000"
+        )));
+        assert!(!is_range_too_large(&rpc(-32000, "execution reverted")));
+    }
+}
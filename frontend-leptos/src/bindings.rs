@@ -0,0 +1,59 @@
+//! Contract bindings that `build.rs` generates from the ABIs in `src-js`.
+//!
+//! Changing an ABI changes these types, so code that no longer matches the contracts fails to compile instead of
+//! failing in the browser.
+//!
+//! Each contract also gets a `{name}Reader` with a typed method for every view function. Those read through a
+//! [`PublicClient`](crate::public_client::PublicClient) instead of viem.
+
+// abigen's code isn't written for clippy
+#[allow(clippy::large_enum_variant, clippy::useless_conversion)]
+pub mod game {
+    include!(concat!(env!("OUT_DIR"), "/alderson_dice_game_v0.rs"));
+}
+
+#[allow(clippy::large_enum_variant, clippy::useless_conversion)]
+pub mod nft {
+    include!(concat!(env!("OUT_DIR"), "/alderson_dice_nft.rs"));
+}
+
+pub use game::{AldersonDiceGameV0Reader as GameReader, DieInfo};
+pub use nft::AldersonDiceNFTReader as NftReader;
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::game::CurrentBagReturn;
+    use super::*;
+    use crate::eip1193::EIP1193Provider;
+    use crate::mock_provider::MockProvider;
+    use crate::public_client::PublicClient;
+    use ethers::abi::AbiEncode;
+    use ethers::types::{Address, Bytes, U256};
+    use serde_json::json;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    async fn reads_the_current_bag() {
+        let bag = vec![U256::from(3), U256::from(7)];
+
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+            "eth_call": Bytes::from(CurrentBagReturn { bag: bag.clone() }.encode()),
+        }));
+
+        let client = PublicClient::new(EIP1193Provider::new(mock.inner()).unwrap());
+
+        let game = GameReader::new(client, Address::repeat_byte(0x11)).at(16u64);
+
+        assert_eq!(game.current_bag().await.unwrap(), bag);
+
+        let request = &mock.requests_for("eth_call")[0];
+
+        // the selector for `currentBag()`
+        assert_eq!(request[0]["data"], json!("0xad7c952d"));
+        assert_eq!(request[1], json!("0x10"));
+    }
+}
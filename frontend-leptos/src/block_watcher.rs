@@ -0,0 +1,261 @@
+//! New block notifications that stop when the watcher is dropped.
+//!
+//! Wallets that support `eth_subscribe` push new heads to us. Everything else is polled by viem at about the chain's
+//! block time.
+use crate::chains::ChainInfo;
+use crate::eip1193::{BlockHeader, EIP1193Provider, Subscription};
+use crate::viem::ViemPublicClient;
+use js_sys::Function;
+use leptos::logging::{log, warn};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::spawn_local;
+
+/// polling faster than this is hard on public rpcs. chains like arbitrum make blocks faster than this
+const MIN_POLLING_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often to poll for new blocks on `chain`.
+pub fn polling_interval(chain: &ChainInfo) -> Duration {
+    chain.block_time.max(MIN_POLLING_INTERVAL)
+}
+
+enum Watch {
+    /// waiting to hear if the wallet supports `newHeads`
+    Starting,
+    /// only held so that it is dropped with the watcher
+    Subscribed {
+        _subscription: Subscription,
+    },
+    Polling {
+        unwatch: Function,
+        client: JsValue,
+    },
+    Stopped,
+}
+
+/// Calls back with every new block until it is dropped.
+#[must_use = "blocks stop being watched when this is dropped"]
+pub struct BlockWatcher {
+    state: Rc<RefCell<Watch>>,
+}
+
+impl BlockWatcher {
+    /// Subscribe to `newHeads` through `provider` if there is one and it supports them. Otherwise poll through
+    /// `public_client`.
+    pub fn new(
+        public_client: ViemPublicClient,
+        provider: Option<EIP1193Provider>,
+        chain: &ChainInfo,
+        emit_missed: bool,
+        on_block: impl Fn(BlockHeader) + 'static,
+    ) -> Self {
+        let on_block: Rc<dyn Fn(BlockHeader)> = Rc::new(on_block);
+
+        let interval = polling_interval(chain);
+
+        let poll = move |public_client: ViemPublicClient, on_block| Watch::Polling {
+            unwatch: public_client.watch_blocks(interval, emit_missed, on_block),
+            client: public_client.inner(),
+        };
+
+        let Some(provider) = provider else {
+            return Self {
+                state: Rc::new(RefCell::new(poll(public_client, on_block))),
+            };
+        };
+
+        let state = Rc::new(RefCell::new(Watch::Starting));
+
+        {
+            let state = state.clone();
+
+            spawn_local(async move {
+                let subscribed = {
+                    let on_block = on_block.clone();
+
+                    provider.subscribe_new_heads(move |x| on_block(x)).await
+                };
+
+                let mut state = state.borrow_mut();
+
+                // dropped while we were waiting. dropping `subscribed` unsubscribes
+                if !matches!(*state, Watch::Starting) {
+                    return;
+                }
+
+                *state = match subscribed {
+                    Ok(x) => {
+                        log!("watching newHeads");
+                        Watch::Subscribed { _subscription: x }
+                    }
+                    Err(err) => {
+                        log!("no newHeads ({}). polling every {:?}", err, interval);
+                        poll(public_client, on_block)
+                    }
+                };
+            });
+        }
+
+        Self { state }
+    }
+
+    pub fn is_subscribed(&self) -> bool {
+        matches!(*self.state.borrow(), Watch::Subscribed { .. })
+    }
+}
+
+impl Drop for BlockWatcher {
+    fn drop(&mut self) {
+        // dropping a subscription unsubscribes on its own
+        let state = std::mem::replace(&mut *self.state.borrow_mut(), Watch::Stopped);
+
+        if let Watch::Polling { unwatch, client } = state {
+            match unwatch.call0(&client) {
+                Ok(_) => log!("stopped polling for blocks"),
+                Err(err) => warn!("failed to unwatch blocks: {:?}", err),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use crate::fallback::FallbackTransport;
+    use crate::mock_provider::{next_tick, sleep, MockProvider};
+    use ethers::types::H256;
+    use serde_json::json;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn header(number: u64) -> serde_json::Value {
+        json!({
+            "number": format!("{:#x}", number),
+            "hash": H256::from_low_u64_be(number),
+            "parentHash": H256::from_low_u64_be(number - 1),
+            "timestamp": "0x665b4c00",
+            "miner": "0x0000000000000000000000000000000000000000",
+            "gasLimit": "0x4000000000000",
+            "gasUsed": "0x0",
+            "baseFeePerGas": "0x989680",
+            "mixHash": H256::repeat_byte(0x33),
+        })
+    }
+
+    fn watch(
+        mock: &MockProvider,
+        subscribe: bool,
+    ) -> (BlockWatcher, Rc<RefCell<Vec<BlockHeader>>>) {
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        let blocks: Rc<RefCell<Vec<BlockHeader>>> = Default::default();
+
+        let watcher = {
+            let blocks = blocks.clone();
+
+            BlockWatcher::new(
+                ViemPublicClient::new(
+                    "0xa4b1".to_string(),
+                    Some(mock.inner()),
+                    Rc::new(FallbackTransport::http(&[])),
+                ),
+                subscribe.then_some(provider),
+                &crate::chains::ARBITRUM,
+                true,
+                move |x| blocks.borrow_mut().push(x),
+            )
+        };
+
+        (watcher, blocks)
+    }
+
+    #[wasm_bindgen_test]
+    async fn subscribes_and_unsubscribes_on_drop() {
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+            "eth_subscribe": "0x9ce59a13059e417087c02d3236a0b1cc",
+            "eth_unsubscribe": true,
+        }));
+
+        let (watcher, blocks) = watch(&mock, true);
+
+        next_tick().await;
+
+        assert!(watcher.is_subscribed());
+
+        mock.emit_subscription("0x9ce59a13059e417087c02d3236a0b1cc", header(16));
+
+        assert_eq!(blocks.borrow()[0].number.as_u64(), 16);
+        assert_eq!(
+            blocks.borrow()[0].prevrandao(),
+            Some(H256::repeat_byte(0x33))
+        );
+
+        drop(watcher);
+
+        next_tick().await;
+
+        assert_eq!(
+            mock.requests_for("eth_unsubscribe"),
+            vec![json!(["0x9ce59a13059e417087c02d3236a0b1cc"])]
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn polls_and_emits_missed_blocks() {
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+        }));
+
+        mock.respond_once("eth_getBlockByNumber", header(16))
+            .respond_once("eth_getBlockByNumber", header(18))
+            .respond_once("eth_getBlockByNumber", header(17))
+            .respond("eth_getBlockByNumber", header(18));
+
+        let (watcher, blocks) = watch(&mock, false);
+
+        // viem polls on its own timer. wait for the blocks instead of guessing how long that takes
+        for _ in 0..100 {
+            if blocks.borrow().len() >= 3 {
+                break;
+            }
+
+            sleep(50).await;
+        }
+
+        let numbers = blocks
+            .borrow()
+            .iter()
+            .map(|x| x.number.as_u64())
+            .collect::<Vec<_>>();
+
+        assert_eq!(numbers, vec![16, 17, 18]);
+
+        drop(watcher);
+
+        let seen = blocks.borrow().len();
+
+        mock.respond("eth_getBlockByNumber", header(19));
+
+        sleep(MIN_POLLING_INTERVAL.as_millis() as i32 * 2).await;
+
+        assert_eq!(blocks.borrow().len(), seen);
+    }
+
+    #[wasm_bindgen_test]
+    fn polls_at_the_block_time() {
+        assert_eq!(
+            polling_interval(&crate::chains::ARBITRUM),
+            MIN_POLLING_INTERVAL
+        );
+        assert_eq!(
+            polling_interval(&crate::chains::MAINNET),
+            Duration::from_secs(12)
+        );
+    }
+}
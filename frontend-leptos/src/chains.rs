@@ -0,0 +1,216 @@
+//! The chains that we know how to talk to.
+//!
+//! This is what we give to `wallet_addEthereumChain` when a wallet has never seen one of our chains.
+//! TODO: keep this in sync with `chainIdToChain` in `src-js/index.ts`
+use crate::storage;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NativeCurrency {
+    pub name: &'static str,
+    pub symbol: &'static str,
+    pub decimals: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainInfo {
+    pub chain_id: u64,
+    pub name: &'static str,
+    pub native_currency: NativeCurrency,
+    /// public rpcs. used when the user hasn't configured their own
+    pub rpc_urls: &'static [&'static str],
+    pub block_explorer_urls: &'static [&'static str],
+    /// about how often a new block is made
+    pub block_time: Duration,
+}
+
+const ETHER: NativeCurrency = NativeCurrency {
+    name: "Ether",
+    symbol: "ETH",
+    decimals: 18,
+};
+
+pub const ARBITRUM: ChainInfo = ChainInfo {
+    chain_id: 0xa4b1,
+    name: "Arbitrum One",
+    native_currency: ETHER,
+    rpc_urls: &[
+        "https://arb1.arbitrum.io/rpc",
+        "https://arbitrum-one-rpc.publicnode.com",
+        "https://arbitrum.drpc.org",
+    ],
+    block_explorer_urls: &["https://arbiscan.io"],
+    block_time: Duration::from_millis(250),
+};
+
+pub const BASE: ChainInfo = ChainInfo {
+    chain_id: 0x2105,
+    name: "Base",
+    native_currency: ETHER,
+    rpc_urls: &[
+        "https://mainnet.base.org",
+        "https://base-rpc.publicnode.com",
+    ],
+    block_explorer_urls: &["https://basescan.org"],
+    block_time: Duration::from_secs(2),
+};
+
+pub const MAINNET: ChainInfo = ChainInfo {
+    chain_id: 0x1,
+    name: "Ethereum",
+    native_currency: ETHER,
+    rpc_urls: &[
+        "https://cloudflare-eth.com",
+        "https://ethereum-rpc.publicnode.com",
+    ],
+    block_explorer_urls: &["https://etherscan.io"],
+    block_time: Duration::from_secs(12),
+};
+
+pub const ALL: [ChainInfo; 3] = [ARBITRUM, BASE, MAINNET];
+
+pub fn chain_info(chain_id: u64) -> Option<&'static ChainInfo> {
+    ALL.iter().find(|x| x.chain_id == chain_id)
+}
+
+/// Where the RPC urls in a [`ChainConfig`] came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RpcSource {
+    /// `?rpc=` in the page's url. only for this page load
+    Query,
+    /// saved with the button in the diagnostics panel
+    Storage,
+    /// [`ChainInfo::rpc_urls`]
+    Default,
+}
+
+/// The RPCs that the app reads a chain from.
+///
+/// Add `?rpc=http://127.0.0.1:8545` to the page's url to use a local node. Repeat it for more than one. These only
+/// last for the page load. A link shouldn't be able to change the RPCs for every later visit, so they are only saved
+/// when the user asks for it in the diagnostics panel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainConfig {
+    pub chain: ChainInfo,
+    pub rpc_urls: Vec<String>,
+    pub source: RpcSource,
+}
+
+impl ChainConfig {
+    pub fn load(chain: ChainInfo) -> Self {
+        Self::from_sources(
+            chain,
+            query_rpc_urls(),
+            storage::load_rpc_urls(chain.chain_id),
+        )
+    }
+
+    /// The query wins over storage, which wins over the chain's defaults.
+    fn from_sources(chain: ChainInfo, query: Vec<String>, saved: Option<Vec<String>>) -> Self {
+        if !query.is_empty() {
+            return Self {
+                chain,
+                rpc_urls: query,
+                source: RpcSource::Query,
+            };
+        }
+
+        if let Some(rpc_urls) = saved.map(valid_rpc_urls).filter(|x| !x.is_empty()) {
+            return Self {
+                chain,
+                rpc_urls,
+                source: RpcSource::Storage,
+            };
+        }
+
+        Self {
+            chain,
+            rpc_urls: chain.rpc_urls.iter().map(|x| x.to_string()).collect(),
+            source: RpcSource::Default,
+        }
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain.chain_id
+    }
+
+    /// Keep using these RPCs on later visits.
+    pub fn save(&self) {
+        storage::save_rpc_urls(self.chain_id(), &self.rpc_urls);
+    }
+}
+
+fn query_rpc_urls() -> Vec<String> {
+    let Some(search) = web_sys::window().and_then(|x| x.location().search().ok()) else {
+        return vec![];
+    };
+
+    let Ok(params) = web_sys::UrlSearchParams::new_with_str(&search) else {
+        return vec![];
+    };
+
+    valid_rpc_urls(params.get_all("rpc").iter().filter_map(|x| x.as_string()))
+}
+
+/// http(s) urls without duplicates. commas also separate urls
+fn valid_rpc_urls(urls: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut valid = Vec::<String>::new();
+
+    for x in urls {
+        for x in x.split(',').map(str::trim) {
+            let is_http = x.starts_with("http://") || x.starts_with("https://");
+
+            if !is_http {
+                if !x.is_empty() {
+                    leptos::logging::warn!("ignoring rpc url: {}", x);
+                }
+                continue;
+            }
+
+            if !valid.iter().any(|y| y == x) {
+                valid.push(x.to_string());
+            }
+        }
+    }
+
+    valid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleans_up_rpc_urls() {
+        let urls = valid_rpc_urls([
+            "http://127.0.0.1:8545".to_string(),
+            " https://arb1.arbitrum.io/rpc , http://127.0.0.1:8545".to_string(),
+            "javascript:alert(1)".to_string(),
+            "".to_string(),
+        ]);
+
+        assert_eq!(
+            urls,
+            vec!["http://127.0.0.1:8545", "https://arb1.arbitrum.io/rpc"]
+        );
+    }
+
+    #[test]
+    fn prefers_the_query_then_saved_urls() {
+        let local = vec!["http://127.0.0.1:8545".to_string()];
+        let saved = vec!["https://arbitrum.drpc.org".to_string()];
+
+        let x = ChainConfig::from_sources(ARBITRUM, local.clone(), Some(saved.clone()));
+
+        assert_eq!((x.rpc_urls, x.source), (local, RpcSource::Query));
+
+        let x = ChainConfig::from_sources(ARBITRUM, vec![], Some(saved.clone()));
+
+        assert_eq!((x.rpc_urls, x.source), (saved, RpcSource::Storage));
+
+        let x = ChainConfig::from_sources(ARBITRUM, vec![], Some(vec!["ftp://x".to_string()]));
+
+        assert_eq!(x.source, RpcSource::Default);
+        assert_eq!(x.rpc_urls.len(), ARBITRUM.rpc_urls.len());
+    }
+}
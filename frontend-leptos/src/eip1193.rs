@@ -1,14 +1,21 @@
 //! <https://github.com/ethereum/EIPs/blob/master/EIPS/eip-1193.md>.
 
+use crate::chains::ChainInfo;
+use crate::revert::Revert;
+use ethers::abi::{AbiDecode, AbiEncode};
+use ethers::types::{
+    Address, BlockId, BlockNumber, Bytes, Filter, Log, TransactionRequest, H256, U256, U64,
+};
 use js_sys::{Function, Promise, Reflect};
 use leptos::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::RwLock;
 use wasm_bindgen::{closure::Closure, JsCast, JsValue};
 
-// TODO: what should this type actually be?
-type SubscriptionCallback = Box<dyn Fn(()) -> Result<(), JsValue>>;
+/// Called with the `result` of every `eth_subscription` message for a subscription.
+type SubscriptionCallback = Rc<dyn Fn(JsValue)>;
 
 // TODO: theres a bunch fields on here, but we don't need them yet
 // TODO: actually i think we should delete some of this and use viem when at all possible
@@ -18,10 +25,16 @@ pub struct EIP1193Provider {
     // TODO: turn this into a function that we can call from rust
     _request: Function,
     _on: Function,
-    _remove_listener: Function,
+    // removed from the provider when the last clone is dropped
+    _listeners: Rc<Listeners>,
 
     // TODO: do we need an async lock here? is Rc okay or do we need Arc?
     subscriptions: Rc<RwLock<HashMap<String, SubscriptionCallback>>>,
+
+    // kept in sync by the provider's events
+    chain_id: RwSignal<Option<u64>>,
+    accounts: RwSignal<Vec<Address>>,
+    connected: RwSignal<bool>,
 }
 
 impl PartialEq for EIP1193Provider {
@@ -52,18 +65,287 @@ interface EthSubscription extends ProviderMessage {
   };
 }
 
-interface ProviderConnectInfo {
-  readonly chainId: string;
+*/
+
+/// <https://eips.ethereum.org/EIPS/eip-1193#provider-errors> and <https://eips.ethereum.org/EIPS/eip-1474#error-codes>
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProviderRpcErrorCode {
+    /// The user rejected the request.
+    UserRejectedRequest,
+    /// The requested method and/or account has not been authorized by the user.
+    Unauthorized,
+    /// The Provider does not support the requested method.
+    UnsupportedMethod,
+    /// The Provider is disconnected from all chains.
+    Disconnected,
+    /// The Provider is not connected to the requested chain.
+    ChainDisconnected,
+    /// Invalid JSON.
+    ParseError,
+    /// JSON is not a valid request object.
+    InvalidRequest,
+    /// Method does not exist.
+    MethodNotFound,
+    /// Invalid method parameters.
+    InvalidParams,
+    /// Internal JSON-RPC error.
+    InternalError,
+    /// Missing or invalid parameters.
+    InvalidInput,
+    /// Requested resource not found.
+    ResourceNotFound,
+    /// Requested resource not available.
+    ResourceUnavailable,
+    /// Transaction creation failed.
+    TransactionRejected,
+    /// Method is not implemented.
+    MethodNotSupported,
+    /// Request exceeds defined limit.
+    LimitExceeded,
+    /// Version of JSON-RPC protocol is not supported.
+    JsonRpcVersionNotSupported,
+    /// The wallet doesn't know about the chain. Add it with `wallet_addEthereumChain`.
+    ///
+    /// This isn't in an EIP, but MetaMask made it the de facto standard.
+    UnrecognizedChainId,
+    /// Any code that isn't in the EIPs. Wallets like to make up their own.
+    Other(i64),
 }
 
-interface ProviderRpcError extends Error {
-  message: string;
-  code: number;
-  data?: unknown;
+impl ProviderRpcErrorCode {
+    pub fn code(&self) -> i64 {
+        match self {
+            Self::UserRejectedRequest => 4001,
+            Self::Unauthorized => 4100,
+            Self::UnsupportedMethod => 4200,
+            Self::Disconnected => 4900,
+            Self::ChainDisconnected => 4901,
+            Self::ParseError => -32700,
+            Self::InvalidRequest => -32600,
+            Self::MethodNotFound => -32601,
+            Self::InvalidParams => -32602,
+            Self::InternalError => -32603,
+            Self::InvalidInput => -32000,
+            Self::ResourceNotFound => -32001,
+            Self::ResourceUnavailable => -32002,
+            Self::TransactionRejected => -32003,
+            Self::MethodNotSupported => -32004,
+            Self::LimitExceeded => -32005,
+            Self::JsonRpcVersionNotSupported => -32006,
+            Self::UnrecognizedChainId => 4902,
+            Self::Other(x) => *x,
+        }
+    }
+}
+
+impl From<i64> for ProviderRpcErrorCode {
+    fn from(code: i64) -> Self {
+        match code {
+            4001 => Self::UserRejectedRequest,
+            4100 => Self::Unauthorized,
+            4200 => Self::UnsupportedMethod,
+            4900 => Self::Disconnected,
+            4901 => Self::ChainDisconnected,
+            -32700 => Self::ParseError,
+            -32600 => Self::InvalidRequest,
+            -32601 => Self::MethodNotFound,
+            -32602 => Self::InvalidParams,
+            -32603 => Self::InternalError,
+            -32000 => Self::InvalidInput,
+            -32001 => Self::ResourceNotFound,
+            -32002 => Self::ResourceUnavailable,
+            -32003 => Self::TransactionRejected,
+            -32004 => Self::MethodNotSupported,
+            -32005 => Self::LimitExceeded,
+            -32006 => Self::JsonRpcVersionNotSupported,
+            4902 => Self::UnrecognizedChainId,
+            x => Self::Other(x),
+        }
+    }
+}
+
+/// The error a provider rejects a request with.
+///
+/// Anything thrown that doesn't have a numeric `code` (a `TypeError` from a broken extension, a response we can't
+/// parse, etc.) ends up in `Js` so that we never have to panic on it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProviderRpcError {
+    Rpc {
+        code: ProviderRpcErrorCode,
+        message: String,
+        data: Option<JsValue>,
+    },
+    Js(JsValue),
+    InvalidRequest(String),
+    InvalidResponse(String),
+    /// the transaction was included but it reverted
+    Reverted(H256),
+    /// simulating the transaction reverted, so it was never sent
+    WouldRevert(Revert),
+    /// we gave up waiting on the wallet or the chain
+    TimedOut(String),
+}
+
+impl ProviderRpcError {
+    pub fn code(&self) -> Option<ProviderRpcErrorCode> {
+        match self {
+            Self::Rpc { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// true if the user clicked "reject" in their wallet. this is a normal thing for a user to do and not a bug
+    pub fn is_user_rejected(&self) -> bool {
+        self.code() == Some(ProviderRpcErrorCode::UserRejectedRequest)
+    }
+
+    /// true if the provider isn't connected to any chain or isn't connected to the chain we asked for
+    pub fn is_disconnected(&self) -> bool {
+        matches!(
+            self.code(),
+            Some(ProviderRpcErrorCode::Disconnected | ProviderRpcErrorCode::ChainDisconnected)
+        )
+    }
+
+    /// true if the wallet needs `wallet_addEthereumChain` before it can switch to the chain
+    pub fn is_unrecognized_chain(&self) -> bool {
+        if self.code() == Some(ProviderRpcErrorCode::UnrecognizedChainId) {
+            return true;
+        }
+
+        // MetaMask mobile wraps the 4902 inside of an internal error
+        let Self::Rpc {
+            data: Some(data), ..
+        } = self
+        else {
+            return false;
+        };
+
+        Reflect::get(data, &"originalError".into())
+            .and_then(|x| Reflect::get(&x, &"code".into()))
+            .ok()
+            .and_then(|x| x.as_f64())
+            == Some(4902.0)
+    }
+}
+
+impl From<JsValue> for ProviderRpcError {
+    fn from(value: JsValue) -> Self {
+        let code = Reflect::get(&value, &"code".into())
+            .ok()
+            .and_then(|x| x.as_f64());
+
+        let Some(code) = code else {
+            return Self::Js(value);
+        };
+
+        let message = Reflect::get(&value, &"message".into())
+            .ok()
+            .and_then(|x| x.as_string())
+            .unwrap_or_default();
+
+        let data = Reflect::get(&value, &"data".into())
+            .ok()
+            .filter(|x| !x.is_undefined());
+
+        Self::Rpc {
+            code: (code as i64).into(),
+            message,
+            data,
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderRpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rpc { code, message, .. } => {
+                write!(f, "{} ({:?}): {}", code.code(), code, message)
+            }
+            Self::Js(x) => write!(f, "javascript error: {:?}", x),
+            Self::InvalidRequest(x) => write!(f, "invalid request: {}", x),
+            Self::InvalidResponse(x) => write!(f, "invalid response: {}", x),
+            Self::Reverted(x) => write!(f, "transaction reverted: {:?}", x),
+            Self::WouldRevert(x) => write!(f, "transaction would fail: {}", x),
+            Self::TimedOut(x) => write!(f, "timed out: {}", x),
+        }
+    }
+}
+
+impl std::error::Error for ProviderRpcError {}
+
+/// The closures that we registered with the provider's `on`.
+///
+/// The provider is cloned all over the place, so these are only removed once the last clone is dropped.
+struct Listeners {
+    target: JsValue,
+    _remove_listener: Function,
+    listeners: Vec<(&'static str, JsValue)>,
+}
+
+impl Listeners {
+    fn add(
+        &mut self,
+        on: &Function,
+        event: &'static str,
+        listener: JsValue,
+    ) -> Result<(), JsValue> {
+        on.call2(&self.target, &event.into(), &listener)?;
+
+        self.listeners.push((event, listener));
+
+        Ok(())
+    }
+}
+
+impl Drop for Listeners {
+    fn drop(&mut self) {
+        for (event, listener) in self.listeners.drain(..) {
+            if let Err(err) = self
+                ._remove_listener
+                .call2(&self.target, &event.into(), &listener)
+            {
+                logging::warn!("failed to remove {} listener: {:?}", event, err);
+            }
+        }
+    }
+}
+
+/// A block without its transactions. This is what a `newHeads` subscription receives. `eth_getBlockByNumber` returns
+/// these fields too.
+///
+/// Some fields are missing on older chains and L2s.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockHeader {
+    pub number: U64,
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub timestamp: U256,
+    pub miner: Address,
+    pub gas_limit: U256,
+    pub gas_used: U256,
+    #[serde(default)]
+    pub base_fee_per_gas: Option<U256>,
+    /// prevrandao after the merge
+    #[serde(default)]
+    pub mix_hash: Option<H256>,
+}
+
+impl BlockHeader {
+    /// The beacon chain's randomness. Chains that aren't proof of stake put something else in `mixHash`.
+    pub fn prevrandao(&self) -> Option<H256> {
+        self.mix_hash
+    }
+}
+
+/// Chain ids come from the wallet as hex strings.
+fn parse_chain_id(chain_id: &JsValue) -> Option<u64> {
+    let chain_id = chain_id.as_string()?;
+
+    u64::from_str_radix(chain_id.strip_prefix("0x")?, 16).ok()
 }
-*/
 
-// TODO: how do we keep set_chain_id and set_accounts working
 impl EIP1193Provider {
     pub fn new(value: JsValue) -> Result<Self, JsValue> {
         let request = Reflect::get(&value, &"request".into()).unwrap();
@@ -78,128 +360,186 @@ impl EIP1193Provider {
 
         let on = on.dyn_into::<Function>()?;
 
+        let remove_listener = Reflect::get(&value, &"removeListener".into()).unwrap();
+
+        let remove_listener = remove_listener.dyn_into::<Function>()?;
+
+        // if anything below fails, dropping this removes whatever was already registered
+        let mut listeners = Listeners {
+            target: value.clone(),
+            _remove_listener: remove_listener,
+            listeners: vec![],
+        };
+
         let subscriptions: Rc<RwLock<HashMap<String, SubscriptionCallback>>> = Default::default();
 
-        // on_chain_changed
-        // TODO: move this to a setter function
-        let on_chain_changed = Closure::wrap(Box::new(move |chain_id: JsValue| {
-            let chain_id = chain_id.as_string().expect("no chain id");
-            logging::log!("chain_id changed: {:?}", chain_id);
+        // these are set by the listeners below. they start empty and get filled in by `sync` once the provider answers
+        let chain_id = create_rw_signal(None);
+        let accounts = create_rw_signal(Vec::new());
+        let connected = create_rw_signal(false);
 
-            // the official docs recommend a reload on chain change. but leptos should keep everything in sync for us
-            // window().location().reload().expect("failed to reload");
+        // on_chain_changed
+        let on_chain_changed = Closure::wrap(Box::new(move |new_chain_id: JsValue| {
+            logging::log!("chain_id changed: {:?}", new_chain_id);
+
+            // the official docs recommend a reload on chain change. but leptos keeps everything in sync for us
+            match parse_chain_id(&new_chain_id) {
+                Some(x) => chain_id.set(Some(x)),
+                None => logging::warn!("invalid chain id: {:?}", new_chain_id),
+            }
         }) as Box<dyn FnMut(JsValue)>);
 
-        let on_chain_changed = on_chain_changed.into_js_value();
-
-        on.call2(&value, &"chainChanged".into(), &on_chain_changed)?;
+        listeners.add(&on, "chainChanged", on_chain_changed.into_js_value())?;
 
         // on_connect
-        let on_connect = Closure::wrap(Box::new(move |connect_object: JsValue| {
-            logging::log!("connected: {:?}", connect_object);
-
-            // TODO: JsValue(Object({"chainId":"0xa4b1"}))
+        let on_connect = Closure::wrap(Box::new(move |connect_info: JsValue| {
+            logging::log!("connected: {:?}", connect_info);
 
-            let chain_id = Reflect::get(&connect_object, &"chainId".into())
-                .expect("no chain id")
-                .as_string()
-                .expect("no chain id");
+            // <https://eips.ethereum.org/EIPS/eip-1193#connect>
+            let new_chain_id = Reflect::get(&connect_info, &"chainId".into())
+                .ok()
+                .and_then(|x| parse_chain_id(&x));
 
-            // set_chain_id(chain_id);
+            connected.set(true);
+            chain_id.set(new_chain_id);
         }) as Box<dyn FnMut(JsValue)>);
 
-        let on_connect = on_connect.into_js_value();
-
-        on.call2(&value, &"connect".into(), &on_connect)?;
+        listeners.add(&on, "connect", on_connect.into_js_value())?;
 
         // on_disconnect
-        let on_disconnect = Closure::wrap(Box::new(move || {
-            logging::log!("disconnected");
-
-            // set_chain_id("".to_string());
-        }) as Box<dyn FnMut()>);
+        let on_disconnect = Closure::wrap(Box::new(move |err: JsValue| {
+            logging::log!("disconnected: {:?}", err);
 
-        let on_disconnect = on_disconnect.into_js_value();
+            connected.set(false);
+            chain_id.set(None);
+        }) as Box<dyn FnMut(JsValue)>);
 
-        on.call2(&value, &"disconnect".into(), &on_disconnect)?;
+        listeners.add(&on, "disconnect", on_disconnect.into_js_value())?;
 
         // on_message (for eth_subscribe)
         let on_message = {
             let subscriptions = subscriptions.clone();
 
             Closure::wrap(Box::new(move |message: JsValue| {
-                logging::log!("message: {:?}", message);
+                // <https://eips.ethereum.org/EIPS/eip-1193#message>
+                let message_type = Reflect::get(&message, &"type".into())
+                    .ok()
+                    .and_then(|x| x.as_string());
+
+                if message_type.as_deref() != Some("eth_subscription") {
+                    logging::log!("message: {:?}", message);
+                    return;
+                }
 
-                // TODO: what do we do here? we need to track subscriptions and call the appropriate callbacks
+                let data = Reflect::get(&message, &"data".into()).unwrap_or_default();
 
-                let data = Reflect::get(&message, &"data".into()).unwrap();
+                let Some(sub_id) = Reflect::get(&data, &"subscription".into())
+                    .ok()
+                    .and_then(|x| x.as_string())
+                else {
+                    logging::warn!("subscription message without an id: {:?}", message);
+                    return;
+                };
 
-                let sub_id = Reflect::get(&data, &"subscription".into())
-                    .unwrap()
-                    .as_string()
-                    .unwrap();
+                let result = Reflect::get(&data, &"result".into()).unwrap_or_default();
 
-                let subscriptions = subscriptions.read().expect("unable to lock subscriptions");
+                // clone the callback out so that the lock isn't held while it runs. the callback might drop its own subscription
+                let sub = subscriptions
+                    .read()
+                    .expect("unable to lock subscriptions")
+                    .get(&sub_id)
+                    .cloned();
 
-                if let Some(sub) = subscriptions.get(&sub_id) {
-                    sub(()).expect("failed to call subscription callback");
+                if let Some(sub) = sub {
+                    sub(result);
                 } else {
                     logging::log!("no subscription found for id: {:?}", sub_id);
                 }
             }) as Box<dyn FnMut(JsValue)>)
         };
 
-        let on_message = on_message.into_js_value();
-
-        on.call2(&value, &"message".into(), &on_message)?;
+        listeners.add(&on, "message", on_message.into_js_value())?;
 
         // on_accounts_changed
-        let on_accounts_changed = Closure::wrap(Box::new(move |accounts: JsValue| {
-            let accounts = accounts
-                .dyn_into::<js_sys::Array>()
-                .expect("Expected an array for accounts");
-
-            // TODO: turn this into an Address object?
-            let accounts: Vec<_> = accounts
-                .iter()
-                .map(|x| x.as_string().expect("account is not a string"))
-                .collect();
+        let on_accounts_changed = Closure::wrap(Box::new(move |new_accounts: JsValue| {
+            let new_accounts: Vec<Address> = match serde_wasm_bindgen::from_value(new_accounts) {
+                Ok(x) => x,
+                Err(err) => {
+                    logging::warn!("invalid accounts: {:?}", err);
+                    return;
+                }
+            };
 
-            logging::log!("accounts changed: {:?}", accounts);
+            logging::log!("accounts changed: {:?}", new_accounts);
 
-            // set_accounts(accounts);
+            accounts.set(new_accounts);
         }) as Box<dyn FnMut(JsValue)>);
 
-        let on_accounts_changed = on_accounts_changed.into_js_value();
-
-        on.call2(&value, &"accountsChanged".into(), &on_accounts_changed)?;
-
-        // remove listeners
-        let remove_listener = Reflect::get(&value, &"removeListener".into()).unwrap();
-
-        let remove_listener = remove_listener.dyn_into::<Function>()?;
+        listeners.add(&on, "accountsChanged", on_accounts_changed.into_js_value())?;
 
-        Ok(Self {
+        let provider = Self {
             _inner: value,
             _request: request,
             _on: on,
-            _remove_listener: remove_listener,
+            _listeners: Rc::new(listeners),
             subscriptions,
-        })
+            chain_id,
+            accounts,
+            connected,
+        };
+
+        provider.sync();
+
+        Ok(provider)
     }
 
     pub fn inner(&self) -> JsValue {
         self._inner.clone()
     }
+
+    /// The chain that the wallet is currently on. `None` until the wallet tells us or if it disconnects.
+    pub fn chain_id_signal(&self) -> ReadSignal<Option<u64>> {
+        self.chain_id.read_only()
+    }
+
+    /// The accounts that the user has connected to this site. This does not prompt the user.
+    pub fn accounts_signal(&self) -> ReadSignal<Vec<Address>> {
+        self.accounts.read_only()
+    }
+
+    pub fn connected_signal(&self) -> ReadSignal<bool> {
+        self.connected.read_only()
+    }
+
+    /// The events only fire on changes, so ask the provider for the current state.
+    fn sync(&self) {
+        let provider = self.clone();
+
+        spawn_local(async move {
+            match provider.chain_id().await {
+                Ok(x) => {
+                    provider.connected.set(true);
+                    provider.chain_id.set(Some(x));
+                }
+                Err(err) => logging::warn!("failed to get chain id: {}", err),
+            }
+
+            if let Err(err) = provider.accounts().await {
+                logging::warn!("failed to get accounts: {}", err);
+            }
+        });
+    }
 }
 
 impl EIP1193Provider {
-    /// TODO: for params, instead of JsValue, take things that impl Serialize
-    pub async fn request(
+    /// Send a request with params that are already javascript values.
+    ///
+    /// Prefer [`Self::request`] unless you already have a `JsValue` from somewhere else.
+    pub async fn request_raw(
         &self,
         method: &str,
         params: Option<&JsValue>,
-    ) -> Result<JsValue, JsValue> {
+    ) -> Result<JsValue, ProviderRpcError> {
         let arg1 = js_sys::Object::new();
 
         js_sys::Reflect::set(&arg1, &"method".into(), &method.into())?;
@@ -218,30 +558,188 @@ impl EIP1193Provider {
         Ok(result)
     }
 
-    pub async fn chain_id(&self) -> Result<u64, JsValue> {
-        let x = self.request("eth_chainId", None).await?;
+    /// Send a request and deserialize the result.
+    ///
+    /// `params` is usually a tuple or a slice since JSON-RPC params are positional. Use `&()` for methods that don't
+    /// take any params.
+    pub async fn request<P: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &P,
+    ) -> Result<R, ProviderRpcError> {
+        let params = params
+            .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+            .map_err(|err| ProviderRpcError::InvalidRequest(format!("{}: {}", method, err)))?;
+
+        let params = if params.is_null() || params.is_undefined() {
+            None
+        } else {
+            Some(params)
+        };
+
+        let result = self.request_raw(method, params.as_ref()).await?;
 
-        let x = x.as_string().unwrap();
+        serde_wasm_bindgen::from_value(result)
+            .map_err(|err| ProviderRpcError::InvalidResponse(format!("{}: {}", method, err)))
+    }
+
+    /// `eth_chainId`
+    pub async fn chain_id(&self) -> Result<u64, ProviderRpcError> {
+        let x: U64 = self.request("eth_chainId", &()).await?;
+
+        Ok(x.as_u64())
+    }
 
-        let x = u64::from_str_radix(&x[2..], 16).unwrap();
+    /// `eth_accounts`. This never prompts the user. It is empty if the user hasn't connected yet.
+    pub async fn accounts(&self) -> Result<Vec<Address>, ProviderRpcError> {
+        let x: Vec<Address> = self.request("eth_accounts", &()).await?;
+
+        // not all wallets emit accountsChanged for this
+        self.accounts.set(x.clone());
+
+        Ok(x)
+    }
+
+    /// `eth_requestAccounts`. This prompts the user to connect if they haven't already.
+    ///
+    /// <https://eips.ethereum.org/EIPS/eip-1102>
+    pub async fn request_accounts(&self) -> Result<Vec<Address>, ProviderRpcError> {
+        let x: Vec<Address> = self.request("eth_requestAccounts", &()).await?;
+
+        // not all wallets emit accountsChanged for this
+        self.accounts.set(x.clone());
 
         Ok(x)
     }
 
-    // TODO: what should the function signature on the callback be? should we use `terrors` instead of JsValue?
-    // TODO: support unsubscribing
-    pub async fn subscribe<F>(
+    /// `eth_blockNumber`
+    pub async fn block_number(&self) -> Result<u64, ProviderRpcError> {
+        let x: U64 = self.request("eth_blockNumber", &()).await?;
+
+        Ok(x.as_u64())
+    }
+
+    /// `eth_call`
+    pub async fn call(
         &self,
-        action: &str,
-        callback: SubscriptionCallback,
-    ) -> Result<(), JsValue> {
-        let action = action.into();
+        tx: &TransactionRequest,
+        block: BlockId,
+    ) -> Result<Bytes, ProviderRpcError> {
+        self.request("eth_call", &(tx, block)).await
+    }
 
-        // TODO: if we have an http connection, this just won't
-        let subscription_id = self.request("eth_subscribe", Some(&action)).await?;
+    /// `eth_call` a contract at the latest block with ABI encoded calldata and decode what it returns.
+    pub async fn call_contract<C: AbiEncode, R: AbiDecode>(
+        &self,
+        to: Address,
+        call: C,
+    ) -> Result<R, ProviderRpcError> {
+        let tx = TransactionRequest::new().to(to).data(call.encode());
+
+        let x = self.call(&tx, BlockNumber::Latest.into()).await?;
+
+        R::decode(x).map_err(|err| ProviderRpcError::InvalidResponse(err.to_string()))
+    }
+
+    /// `eth_getLogs`
+    pub async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>, ProviderRpcError> {
+        self.request("eth_getLogs", &[filter]).await
+    }
+
+    /// `eth_sendTransaction`. Returns the transaction hash.
+    pub async fn send_transaction(
+        &self,
+        tx: &TransactionRequest,
+    ) -> Result<H256, ProviderRpcError> {
+        self.request("eth_sendTransaction", &[tx]).await
+    }
+
+    /// `wallet_switchEthereumChain`
+    ///
+    /// <https://eips.ethereum.org/EIPS/eip-3326>
+    pub async fn switch_chain(&self, chain_id: u64) -> Result<(), ProviderRpcError> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            chain_id: U64,
+        }
+
+        // the result is null on success
+        self.request(
+            "wallet_switchEthereumChain",
+            &[Params {
+                chain_id: chain_id.into(),
+            }],
+        )
+        .await
+    }
 
+    /// `wallet_addEthereumChain`. Most wallets also switch to the chain once it is added.
+    ///
+    /// <https://eips.ethereum.org/EIPS/eip-3085>
+    pub async fn add_chain(&self, chain: &ChainInfo) -> Result<(), ProviderRpcError> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct NativeCurrencyParams<'a> {
+            name: &'a str,
+            symbol: &'a str,
+            decimals: u8,
+        }
+
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params<'a> {
+            chain_id: U64,
+            chain_name: &'a str,
+            native_currency: NativeCurrencyParams<'a>,
+            rpc_urls: &'a [&'a str],
+            block_explorer_urls: &'a [&'a str],
+        }
+
+        let params = Params {
+            chain_id: chain.chain_id.into(),
+            chain_name: chain.name,
+            native_currency: NativeCurrencyParams {
+                name: chain.native_currency.name,
+                symbol: chain.native_currency.symbol,
+                decimals: chain.native_currency.decimals,
+            },
+            rpc_urls: chain.rpc_urls,
+            block_explorer_urls: chain.block_explorer_urls,
+        };
+
+        // the result is null on success
+        self.request("wallet_addEthereumChain", &[params]).await
+    }
+
+    /// Switch to the chain. If the wallet has never seen it, ask the user to add it and then switch again.
+    pub async fn switch_or_add_chain(&self, chain: &ChainInfo) -> Result<(), ProviderRpcError> {
+        match self.switch_chain(chain.chain_id).await {
+            Err(err) if err.is_unrecognized_chain() => {
+                logging::log!("{} is unknown to the wallet. adding it", chain.name);
+
+                self.add_chain(chain).await?;
+
+                // some wallets switch when adding and some don't. switching again is harmless
+                self.switch_chain(chain.chain_id).await
+            }
+            x => x,
+        }
+    }
+
+    /// `eth_subscribe`. The callback receives the `result` of every message for this subscription.
+    ///
+    /// `params` are the full params for `eth_subscribe`. For example, `&["newHeads"]` or `&("logs", filter)`.
+    ///
+    /// The subscription is cancelled when the returned [`Subscription`] is dropped.
+    pub async fn subscribe<P: Serialize + ?Sized>(
+        &self,
+        params: &P,
+        callback: impl Fn(JsValue) + 'static,
+    ) -> Result<Subscription, ProviderRpcError> {
+        // TODO: if we have an http connection, this just won't
         // hex str. we don't need to convert it though
-        let subscription_id = subscription_id.as_string().unwrap();
+        let subscription_id: String = self.request("eth_subscribe", params).await?;
 
         // save the subscription id and callback
         // an "on message" callback is already subscribed
@@ -250,8 +748,414 @@ impl EIP1193Provider {
             .write()
             .expect("unable to lock subscriptions");
 
-        lock.insert(subscription_id, callback);
+        lock.insert(subscription_id.clone(), Rc::new(callback));
 
-        Ok(())
+        Ok(Subscription {
+            id: subscription_id,
+            provider: self.clone(),
+            active: true,
+        })
+    }
+
+    /// [`Self::subscribe`], but with the results deserialized. Results that don't match `T` are logged and skipped.
+    pub async fn subscribe_typed<P: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        params: &P,
+        callback: impl Fn(T) + 'static,
+    ) -> Result<Subscription, ProviderRpcError> {
+        self.subscribe(params, move |result| {
+            match serde_wasm_bindgen::from_value::<T>(result) {
+                Ok(x) => callback(x),
+                Err(err) => logging::warn!(
+                    "invalid subscription result for {}: {}",
+                    std::any::type_name::<T>(),
+                    err
+                ),
+            }
+        })
+        .await
+    }
+
+    /// `eth_subscribe` to `newHeads`. The callback is called with every new block header.
+    pub async fn subscribe_new_heads(
+        &self,
+        callback: impl Fn(BlockHeader) + 'static,
+    ) -> Result<Subscription, ProviderRpcError> {
+        self.subscribe_typed(&["newHeads"], callback).await
+    }
+
+    /// `eth_subscribe` to `logs`. The callback is called with every log that matches the filter.
+    ///
+    /// During a reorg, logs that were already delivered are sent again with `removed` set to true.
+    pub async fn subscribe_logs(
+        &self,
+        filter: &Filter,
+        callback: impl Fn(Log) + 'static,
+    ) -> Result<Subscription, ProviderRpcError> {
+        self.subscribe_typed(&("logs", filter), callback).await
+    }
+
+    /// `eth_subscribe` to `newPendingTransactions`. The callback is called with every pending transaction's hash.
+    pub async fn subscribe_pending_transactions(
+        &self,
+        callback: impl Fn(H256) + 'static,
+    ) -> Result<Subscription, ProviderRpcError> {
+        self.subscribe_typed(&["newPendingTransactions"], callback)
+            .await
+    }
+
+    /// `eth_unsubscribe`. Returns false if the provider didn't know about the subscription.
+    ///
+    /// Usually you want to drop or [`Subscription::cancel`] instead of calling this directly.
+    pub async fn unsubscribe(&self, subscription_id: &str) -> Result<bool, ProviderRpcError> {
+        self.subscriptions
+            .write()
+            .expect("unable to lock subscriptions")
+            .remove(subscription_id);
+
+        self.request("eth_unsubscribe", &[subscription_id]).await
+    }
+}
+
+/// A handle to an `eth_subscribe` subscription. Dropping it sends `eth_unsubscribe`.
+#[must_use = "the subscription is cancelled when this is dropped"]
+pub struct Subscription {
+    id: String,
+    provider: EIP1193Provider,
+    active: bool,
+}
+
+impl Subscription {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Unsubscribe and wait for the provider to confirm it.
+    pub async fn cancel(mut self) -> Result<bool, ProviderRpcError> {
+        // Drop doesn't need to do anything now
+        self.active = false;
+
+        self.provider.unsubscribe(&self.id).await
+    }
+}
+
+impl std::fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscription")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
+
+        // remove the callback now so that no more messages are delivered. the request can finish whenever
+        self.provider
+            .subscriptions
+            .write()
+            .expect("unable to lock subscriptions")
+            .remove(&self.id);
+
+        let provider = self.provider.clone();
+        let id = std::mem::take(&mut self.id);
+
+        spawn_local(async move {
+            if let Err(err) = provider.unsubscribe(&id).await {
+                logging::warn!("failed to unsubscribe from {}: {}", id, err);
+            }
+        });
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use crate::mock_provider::{next_tick, MockProvider};
+    use serde_json::{json, Value};
+    use std::cell::RefCell;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    const EVENTS: [&str; 5] = [
+        "chainChanged",
+        "connect",
+        "disconnect",
+        "message",
+        "accountsChanged",
+    ];
+
+    fn alice() -> Address {
+        "0x00000000000000000000000000000000000a11ce"
+            .parse()
+            .unwrap()
+    }
+
+    fn wallet() -> MockProvider {
+        MockProvider::from_fixtures(json!({
+            "eth_chainId": "0x1",
+            "eth_accounts": [],
+        }))
+    }
+
+    #[wasm_bindgen_test]
+    async fn syncs_state_on_new() {
+        let mock = wallet();
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        next_tick().await;
+
+        assert_eq!(provider.chain_id_signal().get_untracked(), Some(1));
+        assert!(provider.connected_signal().get_untracked());
+        assert!(provider.accounts_signal().get_untracked().is_empty());
+
+        for event in EVENTS {
+            assert_eq!(mock.listener_count(event), 1, "{}", event);
+        }
+
+        // syncing must never prompt the user
+        let methods: Vec<_> = mock.requests().into_iter().map(|(x, _)| x).collect();
+
+        assert_eq!(methods, vec!["eth_chainId", "eth_accounts"]);
+    }
+
+    #[wasm_bindgen_test]
+    async fn connect_switch_and_disconnect() {
+        let mock = wallet();
+
+        mock.respond("eth_requestAccounts", [alice()])
+            .respond("wallet_switchEthereumChain", Value::Null);
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        next_tick().await;
+
+        // connect
+        assert_eq!(provider.request_accounts().await.unwrap(), vec![alice()]);
+        assert_eq!(provider.accounts_signal().get_untracked(), vec![alice()]);
+
+        // switch chain
+        provider.switch_chain(0xa4b1).await.unwrap();
+
+        assert_eq!(
+            mock.requests_for("wallet_switchEthereumChain"),
+            vec![json!([{ "chainId": "0xa4b1" }])]
+        );
+
+        // the signal follows the wallet's event, not our request
+        assert_eq!(provider.chain_id_signal().get_untracked(), Some(1));
+
+        mock.emit_chain_changed(0xa4b1);
+
+        assert_eq!(provider.chain_id_signal().get_untracked(), Some(0xa4b1));
+        assert_eq!(provider.chain_id().await.unwrap(), 0xa4b1);
+
+        // the user disconnects our site from inside their wallet
+        mock.emit_accounts_changed(&[]);
+
+        assert!(provider.accounts_signal().get_untracked().is_empty());
+
+        // the wallet loses its connection
+        mock.emit_disconnect();
+
+        assert!(!provider.connected_signal().get_untracked());
+        assert_eq!(provider.chain_id_signal().get_untracked(), None);
+    }
+
+    #[wasm_bindgen_test]
+    async fn decodes_errors() {
+        let mock = wallet();
+
+        mock.reject_once(
+            "wallet_switchEthereumChain",
+            4001,
+            "User rejected the request.",
+        )
+        .respond_once("wallet_switchEthereumChain", Value::Null);
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        let err = provider.switch_chain(0xa4b1).await.unwrap_err();
+
+        assert!(err.is_user_rejected());
+        assert_eq!(
+            err,
+            ProviderRpcError::Rpc {
+                code: ProviderRpcErrorCode::UserRejectedRequest,
+                message: "User rejected the request.".to_string(),
+                data: None,
+            }
+        );
+
+        // the user tries again and accepts
+        provider.switch_chain(0xa4b1).await.unwrap();
+
+        // nothing is scripted for this
+        let err = provider.block_number().await.unwrap_err();
+
+        assert_eq!(err.code(), Some(ProviderRpcErrorCode::UnsupportedMethod));
+    }
+
+    #[wasm_bindgen_test]
+    async fn adds_unknown_chain() {
+        let mock = wallet();
+
+        mock.reject_once(
+            "wallet_switchEthereumChain",
+            4902,
+            "Unrecognized chain ID \"0xa4b1\".",
+        )
+        .respond("wallet_addEthereumChain", Value::Null)
+        .respond("wallet_switchEthereumChain", Value::Null);
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        provider
+            .switch_or_add_chain(&crate::chains::ARBITRUM)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            mock.requests_for("wallet_addEthereumChain"),
+            vec![json!([{
+                "chainId": "0xa4b1",
+                "chainName": "Arbitrum One",
+                "nativeCurrency": { "name": "Ether", "symbol": "ETH", "decimals": 18 },
+                "rpcUrls": [
+                    "https://arb1.arbitrum.io/rpc",
+                    "https://arbitrum-one-rpc.publicnode.com",
+                    "https://arbitrum.drpc.org",
+                ],
+                "blockExplorerUrls": ["https://arbiscan.io"],
+            }])]
+        );
+        assert_eq!(mock.requests_for("wallet_switchEthereumChain").len(), 2);
+    }
+
+    #[wasm_bindgen_test]
+    async fn user_rejects_adding_chain() {
+        let mock = wallet();
+
+        mock.reject_once("wallet_switchEthereumChain", 4902, "Unrecognized chain ID.")
+            .reject_once(
+                "wallet_addEthereumChain",
+                4001,
+                "User rejected the request.",
+            );
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        let err = provider
+            .switch_or_add_chain(&crate::chains::ARBITRUM)
+            .await
+            .unwrap_err();
+
+        assert!(err.is_user_rejected());
+        assert_eq!(mock.requests_for("wallet_switchEthereumChain").len(), 1);
+    }
+
+    #[wasm_bindgen_test]
+    async fn new_heads_subscription() {
+        let mock = wallet();
+
+        mock.respond("eth_subscribe", "0xabc")
+            .respond("eth_unsubscribe", true);
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        let received = Rc::new(RefCell::new(Vec::new()));
+
+        let sub = {
+            let received = received.clone();
+
+            provider
+                .subscribe_new_heads(move |x| received.borrow_mut().push(x))
+                .await
+                .unwrap()
+        };
+
+        assert_eq!(sub.id(), "0xabc");
+        assert_eq!(
+            mock.requests_for("eth_subscribe"),
+            vec![json!(["newHeads"])]
+        );
+
+        let head = json!({
+            "number": "0x10",
+            "hash": format!("0x{}", "11".repeat(32)),
+            "parentHash": format!("0x{}", "22".repeat(32)),
+            "timestamp": "0x6650c2a0",
+            "miner": format!("{:?}", alice()),
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0x5208",
+            "baseFeePerGas": "0x7",
+        });
+
+        mock.emit_subscription("0xabc", head.clone());
+        // messages for other subscriptions are ignored
+        mock.emit_subscription("0xdef", head.clone());
+
+        assert_eq!(received.borrow().len(), 1);
+        assert_eq!(received.borrow()[0].number, 16.into());
+        assert_eq!(received.borrow()[0].base_fee_per_gas, Some(7.into()));
+        assert_eq!(received.borrow()[0].mix_hash, None);
+
+        drop(sub);
+
+        mock.emit_subscription("0xabc", head);
+
+        assert_eq!(received.borrow().len(), 1);
+
+        next_tick().await;
+
+        assert_eq!(mock.requests_for("eth_unsubscribe"), vec![json!(["0xabc"])]);
+    }
+
+    #[wasm_bindgen_test]
+    async fn cancel_subscription() {
+        let mock = wallet();
+
+        mock.respond("eth_subscribe", "0xabc")
+            .respond("eth_unsubscribe", true);
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        let sub = provider
+            .subscribe_pending_transactions(|_| {})
+            .await
+            .unwrap();
+
+        assert!(sub.cancel().await.unwrap());
+
+        next_tick().await;
+
+        // only once even though the handle was also dropped
+        assert_eq!(mock.requests_for("eth_unsubscribe").len(), 1);
+    }
+
+    #[wasm_bindgen_test]
+    async fn listeners_removed_with_last_clone() {
+        let mock = wallet();
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+        let clone = provider.clone();
+
+        // let the initial sync finish. it holds a clone too
+        next_tick().await;
+
+        drop(provider);
+
+        assert_eq!(mock.listener_count("chainChanged"), 1);
+
+        drop(clone);
+
+        for event in EVENTS {
+            assert_eq!(mock.listener_count(event), 0, "{}", event);
+        }
     }
 }
@@ -0,0 +1,685 @@
+//! <https://eips.ethereum.org/EIPS/eip-5792>
+//!
+//! Buying dice needs an approval and then the buy. Wallets that support `wallet_sendCalls` can do both with one
+//! popup. Everything else gets the calls one after another.
+use crate::eip1193::{EIP1193Provider, ProviderRpcError, ProviderRpcErrorCode};
+use crate::revert::Revert;
+use crate::viem::ViemWalletClient;
+use ethers::abi::AbiEncode;
+use ethers::types::{
+    Address, BlockNumber, Bytes, TransactionReceipt, TransactionRequest, H256, U256, U64,
+};
+use leptos::logging::log;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// how long to wait between checking on pending calls. arbitrum blocks are fast
+const POLL_MS: u32 = 1_000;
+
+/// give up after about 10 minutes. a dropped or replaced transaction would otherwise be waited on forever
+const MAX_POLLS: u32 = 600;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Call {
+    pub to: Address,
+    pub data: Bytes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<U256>,
+    /// true if this call only works once the calls before it are on-chain
+    #[serde(skip)]
+    pub after_previous: bool,
+}
+
+impl Call {
+    pub fn new(to: Address, call: impl AbiEncode) -> Self {
+        Self {
+            to,
+            data: call.encode().into(),
+            value: None,
+            after_previous: false,
+        }
+    }
+
+    /// Don't simulate this call until the calls before it are included. A buy can't succeed before its approval.
+    pub fn after_previous(mut self) -> Self {
+        self.after_previous = true;
+        self
+    }
+
+    pub fn to_transaction(&self, from: Address) -> TransactionRequest {
+        let mut tx = TransactionRequest::new()
+            .from(from)
+            .to(self.to)
+            .data(self.data.clone());
+
+        if let Some(value) = self.value {
+            tx = tx.value(value);
+        }
+
+        tx
+    }
+}
+
+/// What has happened so far with a call of [`BatchCalls::send_calls`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BatchProgress {
+    /// the wallet accepted all of the calls at once
+    Batched { id: String },
+    /// the wallet accepted one call of the sequential fallback
+    Sent {
+        index: usize,
+        total: usize,
+        hash: H256,
+    },
+    /// a call of the sequential fallback was included in a block
+    Confirmed {
+        index: usize,
+        total: usize,
+        hash: H256,
+    },
+    /// the whole batch was included. wallets often use one transaction for all of the calls
+    BatchConfirmed { id: String, hashes: Vec<H256> },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SendCallsParams<'a> {
+    version: &'static str,
+    chain_id: U64,
+    from: Address,
+    calls: &'a [Call],
+}
+
+/// Older wallets return the id directly. Newer ones wrap it in an object.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SendCallsResult {
+    Id(String),
+    Object { id: String },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CallsReceipt {
+    transaction_hash: H256,
+    status: U64,
+}
+
+#[derive(Debug, Deserialize)]
+struct CallsStatus {
+    /// "PENDING" and "CONFIRMED" in the first version of the EIP. numeric codes in newer ones
+    status: Value,
+    #[serde(default)]
+    receipts: Vec<CallsReceipt>,
+}
+
+/// `eth_simulateV1` params. The calls all go in one block, so each one sees what the ones before it did.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SimulateParams {
+    block_state_calls: [SimulateBlock; 1],
+}
+
+#[derive(Serialize)]
+struct SimulateBlock {
+    calls: Vec<TransactionRequest>,
+}
+
+#[derive(Deserialize)]
+struct SimulatedBlock {
+    calls: Vec<SimulatedCall>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SimulatedCall {
+    status: U64,
+    /// the revert data if the call failed
+    return_data: Bytes,
+}
+
+impl CallsStatus {
+    fn is_pending(&self) -> bool {
+        match &self.status {
+            Value::String(x) => x == "PENDING",
+            Value::Number(x) => x.as_u64().map(|x| x < 200).unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    fn is_confirmed(&self) -> bool {
+        match &self.status {
+            Value::String(x) => x == "CONFIRMED",
+            Value::Number(x) => x.as_u64() == Some(200),
+            _ => false,
+        }
+    }
+}
+
+/// Batched calls for anything that can send EIP-1193 requests.
+#[allow(async_fn_in_trait)]
+pub trait BatchCalls {
+    async fn batch_request<P: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &P,
+    ) -> Result<R, ProviderRpcError>;
+
+    /// `wallet_getCapabilities`. The keys are chain ids.
+    ///
+    /// A wallet without EIP-5792 returns an empty map instead of an error.
+    async fn capabilities(
+        &self,
+        account: Address,
+    ) -> Result<HashMap<u64, HashMap<String, Value>>, ProviderRpcError> {
+        let x: HashMap<String, HashMap<String, Value>> = match self
+            .batch_request("wallet_getCapabilities", &[account])
+            .await
+        {
+            Ok(x) => x,
+            Err(err) if is_unsupported(&err) => return Ok(HashMap::new()),
+            Err(err) => return Err(err),
+        };
+
+        x.into_iter()
+            .map(|(chain_id, capabilities)| {
+                let chain_id: U64 = chain_id.parse().map_err(|_| {
+                    ProviderRpcError::InvalidResponse(format!("bad chain id: {}", chain_id))
+                })?;
+
+                Ok((chain_id.as_u64(), capabilities))
+            })
+            .collect()
+    }
+
+    /// true if the wallet can send atomic batches on this chain
+    ///
+    /// Wallets list other capabilities too (paymasters, etc.), so having an entry for the chain isn't enough.
+    async fn supports_batching(
+        &self,
+        account: Address,
+        chain_id: u64,
+    ) -> Result<bool, ProviderRpcError> {
+        let x = self.capabilities(account).await?;
+
+        let supported = x
+            .get(&chain_id)
+            .and_then(|x| x.get("atomicBatch"))
+            .and_then(|x| x.get("supported"))
+            .and_then(|x| x.as_bool());
+
+        Ok(supported == Some(true))
+    }
+
+    /// `wallet_sendCalls`. Returns the id for `wallet_getCallsStatus`.
+    async fn send_calls_batched(
+        &self,
+        chain_id: u64,
+        from: Address,
+        calls: &[Call],
+    ) -> Result<String, ProviderRpcError> {
+        let params = SendCallsParams {
+            version: "1.0",
+            chain_id: chain_id.into(),
+            from,
+            calls,
+        };
+
+        let x: SendCallsResult = self.batch_request("wallet_sendCalls", &[params]).await?;
+
+        match x {
+            SendCallsResult::Id(id) | SendCallsResult::Object { id } => Ok(id),
+        }
+    }
+
+    /// Poll `wallet_getCallsStatus` until the batch is included. Returns the transaction hashes.
+    async fn wait_for_calls(&self, id: &str) -> Result<Vec<H256>, ProviderRpcError> {
+        for _ in 0..MAX_POLLS {
+            let status: CallsStatus = self.batch_request("wallet_getCallsStatus", &[id]).await?;
+
+            if status.is_pending() {
+                gloo_timers::future::TimeoutFuture::new(POLL_MS).await;
+                continue;
+            }
+
+            if let Some(x) = status.receipts.iter().find(|x| x.status.is_zero()) {
+                return Err(ProviderRpcError::Reverted(x.transaction_hash));
+            }
+
+            if !status.is_confirmed() {
+                return Err(ProviderRpcError::InvalidResponse(format!(
+                    "calls {} failed with status {}",
+                    id, status.status
+                )));
+            }
+
+            return Ok(status
+                .receipts
+                .into_iter()
+                .map(|x| x.transaction_hash)
+                .collect());
+        }
+
+        Err(ProviderRpcError::TimedOut(format!(
+            "calls {} are still pending",
+            id
+        )))
+    }
+
+    /// Poll `eth_getTransactionReceipt` until the transaction is included.
+    async fn wait_for_transaction(&self, hash: H256) -> Result<(), ProviderRpcError> {
+        for _ in 0..MAX_POLLS {
+            let receipt: Option<TransactionReceipt> = self
+                .batch_request("eth_getTransactionReceipt", &[hash])
+                .await?;
+
+            match receipt {
+                None => gloo_timers::future::TimeoutFuture::new(POLL_MS).await,
+                Some(x) if x.status == Some(0.into()) => {
+                    return Err(ProviderRpcError::Reverted(hash))
+                }
+                Some(_) => return Ok(()),
+            }
+        }
+
+        Err(ProviderRpcError::TimedOut(format!(
+            "transaction {:?} was never included",
+            hash
+        )))
+    }
+
+    /// `eth_call` at the latest block. Returns [`ProviderRpcError::WouldRevert`] if sending the call would fail.
+    async fn simulate_call(&self, from: Address, call: &Call) -> Result<Bytes, ProviderRpcError> {
+        let tx = call.to_transaction(from);
+
+        self.batch_request("eth_call", &(tx, BlockNumber::Latest))
+            .await
+            .map_err(|err| match Revert::from_rpc_error(&err) {
+                Some(x) => ProviderRpcError::WouldRevert(x),
+                None => err,
+            })
+    }
+
+    /// `eth_simulateV1` with all of the calls in order. A buy sees the approval before it.
+    ///
+    /// Returns false if the node can't simulate calls together. Returns [`ProviderRpcError::WouldRevert`] if any of
+    /// the calls would fail.
+    async fn simulate_calls(
+        &self,
+        from: Address,
+        calls: &[Call],
+    ) -> Result<bool, ProviderRpcError> {
+        let params = SimulateParams {
+            block_state_calls: [SimulateBlock {
+                calls: calls.iter().map(|x| x.to_transaction(from)).collect(),
+            }],
+        };
+
+        let blocks: Vec<SimulatedBlock> = match self
+            .batch_request("eth_simulateV1", &(params, BlockNumber::Latest))
+            .await
+        {
+            Ok(x) => x,
+            Err(err) => {
+                if let Some(x) = Revert::from_rpc_error(&err) {
+                    return Err(ProviderRpcError::WouldRevert(x));
+                }
+
+                log!("can't simulate the calls together: {}", err);
+                return Ok(false);
+            }
+        };
+
+        let results = blocks.into_iter().flat_map(|x| x.calls).collect::<Vec<_>>();
+
+        if results.len() != calls.len() {
+            return Ok(false);
+        }
+
+        if let Some(x) = results.iter().find(|x| x.status.is_zero()) {
+            return Err(ProviderRpcError::WouldRevert(Revert::decode(
+                &x.return_data,
+            )));
+        }
+
+        Ok(true)
+    }
+
+    /// Send all the calls with one popup if the wallet can. Otherwise send them one at a time.
+    ///
+    /// In the fallback, each call waits for the one before it to be included. The approval has to be on-chain before
+    /// the wallet can estimate gas for the buy.
+    ///
+    /// Every call is simulated before it is sent, so a call that would revert never gets a popup. Calls marked
+    /// [`Call::after_previous`] only work after the calls before them. They are batched only if the node can simulate
+    /// the whole batch. Otherwise they go one at a time and each is simulated once the calls before it are included.
+    async fn send_calls(
+        &self,
+        chain_id: u64,
+        from: Address,
+        calls: &[Call],
+        progress: impl Fn(BatchProgress),
+    ) -> Result<Vec<H256>, ProviderRpcError> {
+        let total = calls.len();
+
+        for call in calls.iter().filter(|x| !x.after_previous) {
+            self.simulate_call(from, call).await?;
+        }
+
+        let batch = self.supports_batching(from, chain_id).await?
+            && (!calls.iter().any(|x| x.after_previous)
+                || self.simulate_calls(from, calls).await?);
+
+        if batch {
+            let id = self.send_calls_batched(chain_id, from, calls).await?;
+
+            progress(BatchProgress::Batched { id: id.clone() });
+
+            let hashes = self.wait_for_calls(&id).await?;
+
+            progress(BatchProgress::BatchConfirmed {
+                id,
+                hashes: hashes.clone(),
+            });
+
+            return Ok(hashes);
+        }
+
+        let mut hashes = Vec::with_capacity(total);
+
+        for (index, call) in calls.iter().enumerate() {
+            // the calls before this one are included now
+            if call.after_previous {
+                self.simulate_call(from, call).await?;
+            }
+
+            let tx = call.to_transaction(from);
+
+            let hash: H256 = self.batch_request("eth_sendTransaction", &[tx]).await?;
+
+            progress(BatchProgress::Sent { index, total, hash });
+
+            self.wait_for_transaction(hash).await?;
+
+            progress(BatchProgress::Confirmed { index, total, hash });
+
+            hashes.push(hash);
+        }
+
+        Ok(hashes)
+    }
+}
+
+fn is_unsupported(err: &ProviderRpcError) -> bool {
+    matches!(
+        err.code(),
+        Some(
+            ProviderRpcErrorCode::UnsupportedMethod
+                | ProviderRpcErrorCode::MethodNotFound
+                | ProviderRpcErrorCode::MethodNotSupported
+        )
+    )
+}
+
+impl BatchCalls for EIP1193Provider {
+    async fn batch_request<P: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &P,
+    ) -> Result<R, ProviderRpcError> {
+        self.request(method, params).await
+    }
+}
+
+impl BatchCalls for ViemWalletClient {
+    async fn batch_request<P: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &P,
+    ) -> Result<R, ProviderRpcError> {
+        self.request(method, params).await
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+    use serde_json::json;
+    use std::cell::RefCell;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn alice() -> Address {
+        "0x00000000000000000000000000000000000a11ce"
+            .parse()
+            .unwrap()
+    }
+
+    fn calls() -> Vec<Call> {
+        vec![
+            Call::new(alice(), (1u64,)),
+            Call::new(alice(), (2u64,)).after_previous(),
+        ]
+    }
+
+    fn receipt(hash: H256) -> Value {
+        json!({
+            "transactionHash": hash,
+            "transactionIndex": "0x0",
+            "blockHash": H256::repeat_byte(0xbb),
+            "blockNumber": "0x10",
+            "from": alice(),
+            "to": alice(),
+            "cumulativeGasUsed": "0x5208",
+            "gasUsed": "0x5208",
+            "contractAddress": null,
+            "logs": [],
+            "logsBloom": format!("0x{}", "0".repeat(512)),
+            "status": "0x1",
+        })
+    }
+
+    #[wasm_bindgen_test]
+    async fn sends_batch_when_supported() {
+        let hash = H256::repeat_byte(1);
+
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+            "wallet_getCapabilities": { "0xa4b1": { "atomicBatch": { "supported": true } } },
+            "eth_call": "0x",
+            "eth_simulateV1": [{ "calls": [
+                { "status": "0x1", "returnData": "0x" },
+                { "status": "0x1", "returnData": "0x" },
+            ] }],
+            "wallet_sendCalls": "batch-1",
+            "wallet_getCallsStatus": {
+                "status": "CONFIRMED",
+                "receipts": [{ "transactionHash": hash, "status": "0x1" }],
+            },
+        }));
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        let progress = RefCell::new(vec![]);
+
+        let hashes = provider
+            .send_calls(0xa4b1, alice(), &calls(), |x| progress.borrow_mut().push(x))
+            .await
+            .unwrap();
+
+        assert_eq!(hashes, vec![hash]);
+        assert!(mock.requests_for("eth_sendTransaction").is_empty());
+
+        // the buy is simulated after the approval
+        assert_eq!(
+            mock.requests_for("eth_simulateV1")[0][0]["blockStateCalls"][0]["calls"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            mock.requests_for("wallet_sendCalls")[0][0]["chainId"],
+            json!("0xa4b1")
+        );
+        // one receipt for both calls
+        assert_eq!(
+            progress.into_inner(),
+            vec![
+                BatchProgress::Batched {
+                    id: "batch-1".to_string()
+                },
+                BatchProgress::BatchConfirmed {
+                    id: "batch-1".to_string(),
+                    hashes: vec![hash],
+                },
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn falls_back_to_sequential() {
+        let (first, second) = (H256::repeat_byte(1), H256::repeat_byte(2));
+
+        // capabilities for the chain, but not atomic batches
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+            "eth_call": "0x",
+            "wallet_getCapabilities": { "0xa4b1": { "paymasterService": { "supported": true } } },
+        }));
+
+        mock.respond_once("eth_sendTransaction", first);
+        mock.respond_once("eth_sendTransaction", second);
+        mock.respond_once("eth_getTransactionReceipt", receipt(first));
+        mock.respond_once("eth_getTransactionReceipt", receipt(second));
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        let progress = RefCell::new(vec![]);
+
+        let hashes = provider
+            .send_calls(0xa4b1, alice(), &calls(), |x| progress.borrow_mut().push(x))
+            .await
+            .unwrap();
+
+        assert_eq!(hashes, vec![first, second]);
+        assert!(mock.requests_for("wallet_sendCalls").is_empty());
+        assert_eq!(
+            progress.into_inner(),
+            vec![
+                BatchProgress::Sent {
+                    index: 0,
+                    total: 2,
+                    hash: first
+                },
+                BatchProgress::Confirmed {
+                    index: 0,
+                    total: 2,
+                    hash: first
+                },
+                BatchProgress::Sent {
+                    index: 1,
+                    total: 2,
+                    hash: second
+                },
+                BatchProgress::Confirmed {
+                    index: 1,
+                    total: 2,
+                    hash: second
+                },
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn refuses_calls_that_would_revert() {
+        use crate::bindings::game::Unauthorized;
+        use crate::revert::Revert;
+
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+            "wallet_getCapabilities": { "0xa4b1": { "atomicBatch": { "supported": true } } },
+        }));
+
+        mock.revert_once("eth_call", Unauthorized.encode());
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        let err = provider
+            .send_calls(0xa4b1, alice(), &calls(), |_| {})
+            .await
+            .unwrap_err();
+
+        assert_eq!(err, ProviderRpcError::WouldRevert(Revert::Unauthorized));
+        assert!(mock.requests_for("wallet_sendCalls").is_empty());
+        assert!(mock.requests_for("eth_sendTransaction").is_empty());
+
+        // only the first call can be simulated before anything is sent
+        assert_eq!(mock.requests_for("eth_call").len(), 1);
+    }
+
+    #[wasm_bindgen_test]
+    async fn refuses_batches_that_would_revert() {
+        use crate::bindings::game::Unauthorized;
+        use crate::revert::Revert;
+
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+            "wallet_getCapabilities": { "0xa4b1": { "atomicBatch": { "supported": true } } },
+            "eth_call": "0x",
+            "eth_simulateV1": [{ "calls": [
+                { "status": "0x1", "returnData": "0x" },
+                { "status": "0x0", "returnData": Bytes::from(Unauthorized.encode()) },
+            ] }],
+        }));
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        let err = provider
+            .send_calls(0xa4b1, alice(), &calls(), |_| {})
+            .await
+            .unwrap_err();
+
+        assert_eq!(err, ProviderRpcError::WouldRevert(Revert::Unauthorized));
+        assert!(mock.requests_for("wallet_sendCalls").is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    async fn goes_one_at_a_time_when_the_batch_cant_be_simulated() {
+        let (first, second) = (H256::repeat_byte(1), H256::repeat_byte(2));
+
+        // eth_simulateV1 isn't scripted, so the mock doesn't support it
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+            "eth_call": "0x",
+            "wallet_getCapabilities": { "0xa4b1": { "atomicBatch": { "supported": true } } },
+        }));
+
+        mock.respond_once("eth_sendTransaction", first);
+        mock.respond_once("eth_sendTransaction", second);
+        mock.respond_once("eth_getTransactionReceipt", receipt(first));
+        mock.respond_once("eth_getTransactionReceipt", receipt(second));
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        let hashes = provider
+            .send_calls(0xa4b1, alice(), &calls(), |_| {})
+            .await
+            .unwrap();
+
+        assert_eq!(hashes, vec![first, second]);
+        assert!(mock.requests_for("wallet_sendCalls").is_empty());
+
+        // the buy was simulated once the approval was included
+        assert_eq!(mock.requests_for("eth_call").len(), 2);
+    }
+}
@@ -1,9 +1,436 @@
+//! Multi injected provider discovery. <https://eips.ethereum.org/EIPS/eip-6963>
+use crate::eip1193::EIP1193Provider;
+use js_sys::Reflect;
 use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 pub struct EIP6963ProviderInfo {
     uuid: String,
     name: String,
     icon: String,
     rdns: String,
 }
+
+impl EIP6963ProviderInfo {
+    /// unique for every page load. don't save this
+    pub fn uuid(&self) -> &str {
+        &self.uuid
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// a data uri from the wallet. this can be anything! use [`Self::safe_icon`] to render it
+    pub fn icon(&self) -> &str {
+        &self.icon
+    }
+
+    /// The icon if it is an image that is safe to render.
+    pub fn safe_icon(&self) -> Option<String> {
+        if self.icon.is_empty() {
+            return None;
+        }
+
+        match crate::icon::sanitize_icon(&self.icon) {
+            Ok(x) => Some(x),
+            Err(err) => {
+                leptos::logging::warn!("ignoring icon for {}: {}", self.name, err);
+                None
+            }
+        }
+    }
+
+    /// reverse dns name of the wallet like "io.metamask". this is stable across page loads
+    pub fn rdns(&self) -> &str {
+        &self.rdns
+    }
+
+    /// Parse just the `info` of an `eip6963:announceProvider` event's `detail`.
+    pub fn from_event_detail(detail: &JsValue) -> Result<Self, JsValue> {
+        let info = Reflect::get(detail, &"info".into())?;
+
+        Ok(serde_wasm_bindgen::from_value(info)?)
+    }
+}
+
+/// What a wallet sends with `eip6963:announceProvider`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EIP6963ProviderDetail {
+    info: EIP6963ProviderInfo,
+    provider: EIP1193Provider,
+    /// true if this came from `window.ethereum` instead of an announcement
+    legacy: bool,
+}
+
+impl EIP6963ProviderDetail {
+    pub fn new(info: EIP6963ProviderInfo, provider: EIP1193Provider) -> Self {
+        Self {
+            info,
+            provider,
+            legacy: false,
+        }
+    }
+
+    /// Wrap a provider that was injected at `window.ethereum`. These don't tell us anything about themselves, so the
+    /// name is a guess from the flags that wallets set and there is no icon.
+    pub fn legacy(index: usize, provider: JsValue) -> Result<Self, JsValue> {
+        let info = EIP6963ProviderInfo {
+            uuid: format!("legacy-{}", index),
+            name: legacy_name(&provider).to_string(),
+            icon: "".to_string(),
+            rdns: "".to_string(),
+        };
+
+        let provider = EIP1193Provider::new(provider)?;
+
+        Ok(Self {
+            info,
+            provider,
+            legacy: true,
+        })
+    }
+
+    /// Parse the `detail` of an `eip6963:announceProvider` event.
+    pub fn from_event_detail(detail: &JsValue) -> Result<Self, JsValue> {
+        let info = EIP6963ProviderInfo::from_event_detail(detail)?;
+
+        Self::from_info(info, detail)
+    }
+
+    fn from_info(info: EIP6963ProviderInfo, detail: &JsValue) -> Result<Self, JsValue> {
+        let provider = Reflect::get(detail, &"provider".into())?;
+
+        let provider = EIP1193Provider::new(provider)?;
+
+        Ok(Self::new(info, provider))
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+
+    pub fn info(&self) -> &EIP6963ProviderInfo {
+        &self.info
+    }
+
+    pub fn provider(&self) -> &EIP1193Provider {
+        &self.provider
+    }
+
+    pub fn uuid(&self) -> &str {
+        self.info.uuid()
+    }
+
+    pub fn name(&self) -> &str {
+        self.info.name()
+    }
+
+    pub fn icon(&self) -> &str {
+        self.info.icon()
+    }
+
+    pub fn safe_icon(&self) -> Option<String> {
+        self.info.safe_icon()
+    }
+
+    pub fn rdns(&self) -> &str {
+        self.info.rdns()
+    }
+}
+
+fn legacy_name(provider: &JsValue) -> &'static str {
+    let flag = |x: &str| {
+        Reflect::get(provider, &x.into())
+            .ok()
+            .and_then(|x| x.as_bool())
+            .unwrap_or(false)
+    };
+
+    // some wallets also set isMetaMask for compatibility, so check it last
+    if flag("isCoinbaseWallet") {
+        "Coinbase Wallet"
+    } else if flag("isRabby") {
+        "Rabby"
+    } else if flag("isFrame") {
+        "Frame"
+    } else if flag("isBraveWallet") {
+        "Brave Wallet"
+    } else if flag("isMetaMask") {
+        "MetaMask"
+    } else {
+        "Browser Wallet"
+    }
+}
+
+/// The providers injected at `window.ethereum` by wallets that don't do EIP-6963.
+///
+/// When several extensions fight over `window.ethereum`, some of them put all of the providers in a `providers` array.
+pub fn legacy_providers() -> Vec<EIP6963ProviderDetail> {
+    let Some(ethereum) = web_sys::window()
+        .and_then(|x| Reflect::get(&x, &"ethereum".into()).ok())
+        .filter(|x| x.is_object())
+    else {
+        return vec![];
+    };
+
+    legacy_providers_from(ethereum)
+}
+
+fn legacy_providers_from(ethereum: JsValue) -> Vec<EIP6963ProviderDetail> {
+    let mut providers: Vec<JsValue> = Reflect::get(&ethereum, &"providers".into())
+        .ok()
+        .filter(js_sys::Array::is_array)
+        .map(|x| x.unchecked_into::<js_sys::Array>().to_vec())
+        .unwrap_or_default();
+
+    if providers.is_empty() {
+        providers.push(ethereum);
+    }
+
+    // the same object can be in the array more than once
+    let mut unique = Vec::with_capacity(providers.len());
+
+    for x in providers {
+        if !unique.contains(&x) {
+            unique.push(x);
+        }
+    }
+
+    unique
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, x)| match EIP6963ProviderDetail::legacy(i, x) {
+            Ok(x) => Some(x),
+            Err(err) => {
+                leptos::logging::warn!("invalid legacy provider: {:?}", err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Add `detail` unless a provider with the same uuid is already known. Wallets announce again every time anyone
+/// dispatches `eip6963:requestProvider`.
+///
+/// Wallets that announce themselves usually inject the same provider at `window.ethereum` too. An announcement that
+/// shows up after we gave up and used `window.ethereum` replaces that legacy entry instead of listing the wallet twice.
+///
+/// Returns true if it was added or replaced a legacy entry.
+pub fn insert_unique(all: &mut Vec<EIP6963ProviderDetail>, detail: EIP6963ProviderDetail) -> bool {
+    if all.iter().any(|x| x.uuid() == detail.uuid()) {
+        return false;
+    }
+
+    if let Some(x) = all.iter_mut().find(|x| x.provider() == detail.provider()) {
+        if x.is_legacy() && !detail.is_legacy() {
+            *x = detail;
+            return true;
+        }
+
+        return false;
+    }
+
+    all.push(detail);
+
+    true
+}
+
+/// Wallets announce again every time anyone dispatches `eip6963:requestProvider`. Building an [`EIP1193Provider`]
+/// sends requests to the wallet and adds listeners, so repeats are skipped before that happens.
+#[derive(Debug, Default)]
+pub struct Announcements {
+    seen: HashSet<String>,
+}
+
+impl Announcements {
+    /// Parse an `eip6963:announceProvider` event's `detail`. None if this uuid was already announced.
+    pub fn parse(&mut self, detail: &JsValue) -> Result<Option<EIP6963ProviderDetail>, JsValue> {
+        let info = EIP6963ProviderInfo::from_event_detail(detail)?;
+
+        if self.seen.contains(info.uuid()) {
+            return Ok(None);
+        }
+
+        let uuid = info.uuid().to_string();
+
+        let x = EIP6963ProviderDetail::from_info(info, detail)?;
+
+        // only after it parsed. a broken announcement shouldn't hide a fixed one
+        self.seen.insert(uuid);
+
+        Ok(Some(x))
+    }
+}
+
+/// Listen for wallets announcing themselves and then ask them all to announce.
+pub fn request_providers(on_announce: impl Fn(EIP6963ProviderDetail) + 'static) {
+    let the_window = web_sys::window().expect("no global `window` exists");
+
+    let announcements = RefCell::new(Announcements::default());
+
+    let callback = Closure::wrap(Box::new(move |event: web_sys::CustomEvent| {
+        // bind the result so the borrow ends before `on_announce` runs
+        let x = announcements.borrow_mut().parse(&event.detail());
+
+        match x {
+            Ok(Some(x)) => on_announce(x),
+            Ok(None) => {}
+            Err(err) => leptos::logging::warn!("invalid provider announcement: {:?}", err),
+        }
+    }) as Box<dyn FnMut(_)>);
+
+    the_window
+        .add_event_listener_with_callback(
+            "eip6963:announceProvider",
+            callback.into_js_value().unchecked_ref(),
+        )
+        .expect("failed to add event listener");
+
+    let request_provider_event = web_sys::CustomEvent::new("eip6963:requestProvider")
+        .expect("failed to create custom event");
+
+    the_window
+        .dispatch_event(&request_provider_event)
+        .expect("failed to dispatch event");
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use crate::mock_provider::{next_tick, MockProvider};
+    use js_sys::Object;
+    use serde::Serialize;
+    use serde_json::json;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn announcement(mock: &MockProvider, uuid: &str) -> JsValue {
+        let info = json!({
+            "uuid": uuid,
+            "name": "Mock Wallet",
+            "icon": "data:image/png;base64,AA==",
+            "rdns": "com.example.mock",
+        });
+
+        let detail = Object::new();
+
+        let info = info
+            .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+            .unwrap();
+
+        Reflect::set(&detail, &"info".into(), &info).unwrap();
+        Reflect::set(&detail, &"provider".into(), &mock.inner()).unwrap();
+
+        detail.into()
+    }
+
+    fn announce(mock: &MockProvider, uuid: &str) -> EIP6963ProviderDetail {
+        EIP6963ProviderDetail::from_event_detail(&announcement(mock, uuid)).unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    async fn dedupes_by_uuid() {
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+        }));
+
+        let other = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+        }));
+
+        let first = announce(&mock, "350670db-19fa-4704-a166-e52e178b59d2");
+
+        assert_eq!(first.name(), "Mock Wallet");
+        assert_eq!(first.rdns(), "com.example.mock");
+
+        let mut all = vec![];
+
+        assert!(insert_unique(&mut all, first.clone()));
+        assert!(!insert_unique(&mut all, first));
+        assert!(insert_unique(
+            &mut all,
+            announce(&other, "e2b1d2a4-7b1c-4bde-9b53-6f0f7f1c2d3e")
+        ));
+
+        assert_eq!(all.len(), 2);
+    }
+
+    #[wasm_bindgen_test]
+    async fn skips_repeat_announcements_before_building_a_provider() {
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+        }));
+
+        let detail = announcement(&mock, "350670db-19fa-4704-a166-e52e178b59d2");
+
+        let mut announcements = Announcements::default();
+
+        // dropping the provider would remove its listeners
+        let first = announcements.parse(&detail).unwrap();
+
+        assert!(first.is_some());
+        assert!(announcements.parse(&detail).unwrap().is_none());
+
+        next_tick().await;
+
+        // only the first announcement synced with the wallet
+        assert_eq!(mock.requests_for("eth_chainId").len(), 1);
+        assert_eq!(mock.listener_count("chainChanged"), 1);
+    }
+
+    #[wasm_bindgen_test]
+    async fn announcements_replace_legacy_providers() {
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+        }));
+
+        let other = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+        }));
+
+        // some extensions list every provider at `window.ethereum.providers`, sometimes more than once
+        let ethereum = Object::new();
+        let providers = js_sys::Array::of3(&mock.inner(), &mock.inner(), &other.inner());
+
+        Reflect::set(&ethereum, &"providers".into(), &providers).unwrap();
+
+        let legacy = legacy_providers_from(ethereum.into());
+
+        assert_eq!(
+            legacy.iter().map(|x| x.uuid()).collect::<Vec<_>>(),
+            vec!["legacy-0", "legacy-1"]
+        );
+        assert!(legacy.iter().all(|x| x.is_legacy()));
+
+        let mut all = vec![];
+
+        for x in legacy {
+            assert!(insert_unique(&mut all, x));
+        }
+
+        // the wallet announces after the timeout. it takes the legacy entry's place
+        let announced = announce(&mock, "350670db-19fa-4704-a166-e52e178b59d2");
+
+        assert!(insert_unique(&mut all, announced));
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].rdns(), "com.example.mock");
+        assert!(!all[0].is_legacy());
+
+        // the same object from `window.ethereum` again doesn't replace the announcement
+        let again = EIP6963ProviderDetail::legacy(5, mock.inner()).unwrap();
+
+        assert!(!insert_unique(&mut all, again));
+        assert_eq!(all.len(), 2);
+        assert!(!all[0].is_legacy());
+    }
+}
@@ -0,0 +1,319 @@
+//! Typed structured data signing. <https://eips.ethereum.org/EIPS/eip-712>
+//!
+//! Structs declared with [`eip712_struct!`] know their own EIP-712 type, so they can be signed with
+//! `eth_signTypedData_v4` and the signer can be recovered locally before the signature is used anywhere.
+use crate::eip1193::ProviderRpcError;
+use crate::viem::ViemWalletClient;
+use derive_more::From;
+use ethers::types::transaction::eip712::{
+    EIP712Domain, Eip712, Eip712DomainType, Eip712Error, TypedData, Types,
+};
+use ethers::types::{Address, Bytes, Signature, SignatureError, H256, U256};
+use serde::Serialize;
+
+/// The solidity name of a type that can be a field of an EIP-712 struct.
+pub trait SolidityType {
+    const NAME: &'static str;
+}
+
+macro_rules! solidity_type {
+    ($($ty:ty => $name:literal),* $(,)?) => {
+        $(impl SolidityType for $ty {
+            const NAME: &'static str = $name;
+        })*
+    };
+}
+
+solidity_type!(
+    Address => "address",
+    bool => "bool",
+    Bytes => "bytes",
+    H256 => "bytes32",
+    String => "string",
+    u8 => "uint8",
+    u64 => "uint64",
+    U256 => "uint256",
+);
+
+/// A struct that can be signed with EIP-712. Use [`eip712_struct!`] instead of implementing this by hand.
+///
+/// TODO: nested structs and arrays
+pub trait TypedStruct: Serialize {
+    const PRIMARY_TYPE: &'static str;
+
+    /// `(name, solidity type)` in declaration order. The order is part of the type hash.
+    fn fields() -> Vec<(&'static str, &'static str)>;
+}
+
+/// Declare a struct along with its EIP-712 type. Field names are camelCased like they would be in solidity.
+///
+/// ```ignore
+/// eip712_struct! {
+///     pub struct Offer {
+///         pub maker: Address,
+///         pub amount: U256,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! eip712_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $ty),*
+        }
+
+        impl $crate::eip712::TypedStruct for $name {
+            const PRIMARY_TYPE: &'static str = stringify!($name);
+
+            fn fields() -> Vec<(&'static str, &'static str)> {
+                vec![$((stringify!($field), <$ty as $crate::eip712::SolidityType>::NAME)),*]
+            }
+        }
+    };
+}
+
+eip712_struct! {
+    /// <https://eips.ethereum.org/EIPS/eip-2612>
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Permit {
+        pub owner: Address,
+        pub spender: Address,
+        pub value: U256,
+        pub nonce: U256,
+        pub deadline: U256,
+    }
+}
+
+#[derive(Debug, From)]
+pub enum TypedDataError {
+    Provider(ProviderRpcError),
+    Eip712(Eip712Error),
+    Signature(SignatureError),
+    /// the wallet signed with a different account than the one we asked for
+    WrongSigner {
+        expected: Address,
+        recovered: Address,
+    },
+}
+
+impl std::fmt::Display for TypedDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Provider(x) => write!(f, "{}", x),
+            Self::Eip712(x) => write!(f, "bad typed data: {}", x),
+            Self::Signature(x) => write!(f, "bad signature: {}", x),
+            Self::WrongSigner {
+                expected,
+                recovered,
+            } => write!(f, "expected {:?} to sign, not {:?}", expected, recovered),
+        }
+    }
+}
+
+impl std::error::Error for TypedDataError {}
+
+fn snake_to_camel(x: &str) -> String {
+    let mut out = String::with_capacity(x.len());
+
+    let mut upper = false;
+
+    for c in x.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+/// Wallets want the domain's type listed along with everything else.
+fn domain_type(domain: &EIP712Domain) -> Vec<Eip712DomainType> {
+    [
+        ("name", "string", domain.name.is_some()),
+        ("version", "string", domain.version.is_some()),
+        ("chainId", "uint256", domain.chain_id.is_some()),
+        (
+            "verifyingContract",
+            "address",
+            domain.verifying_contract.is_some(),
+        ),
+        ("salt", "bytes32", domain.salt.is_some()),
+    ]
+    .into_iter()
+    .filter(|(_, _, used)| *used)
+    .map(|(name, r#type, _)| Eip712DomainType {
+        name: name.to_string(),
+        r#type: r#type.to_string(),
+    })
+    .collect()
+}
+
+/// Build the full `eth_signTypedData_v4` payload for a message.
+pub fn typed_data<T: TypedStruct>(
+    domain: EIP712Domain,
+    message: &T,
+) -> Result<TypedData, Eip712Error> {
+    let fields = T::fields()
+        .into_iter()
+        .map(|(name, r#type)| Eip712DomainType {
+            name: snake_to_camel(name),
+            r#type: r#type.to_string(),
+        })
+        .collect();
+
+    let mut types = Types::new();
+
+    types.insert("EIP712Domain".to_string(), domain_type(&domain));
+    types.insert(T::PRIMARY_TYPE.to_string(), fields);
+
+    let message = match serde_json::to_value(message)? {
+        serde_json::Value::Object(x) => x.into_iter().collect(),
+        _ => return Err(Eip712Error::Message("message is not a struct".to_string())),
+    };
+
+    Ok(TypedData {
+        domain,
+        types,
+        primary_type: T::PRIMARY_TYPE.to_string(),
+        message,
+    })
+}
+
+/// Recover the account that signed a message.
+pub fn recover_typed_data<T: TypedStruct>(
+    domain: EIP712Domain,
+    message: &T,
+    signature: &Signature,
+) -> Result<Address, TypedDataError> {
+    let typed_data = typed_data(domain, message)?;
+
+    let hash = typed_data.encode_eip712()?;
+
+    Ok(signature.recover(H256(hash))?)
+}
+
+impl ViemWalletClient {
+    /// `eth_signTypedData_v4`. The signature is checked locally before it is returned.
+    pub async fn sign_typed_data<T: TypedStruct>(
+        &self,
+        account: Address,
+        domain: EIP712Domain,
+        message: &T,
+    ) -> Result<Signature, TypedDataError> {
+        let typed_data = typed_data(domain.clone(), message)?;
+
+        // v4 takes the typed data as a json string
+        let json = serde_json::to_string(&typed_data).map_err(Eip712Error::from)?;
+
+        let signature: String = self
+            .request("eth_signTypedData_v4", &(account, json))
+            .await?;
+
+        let signature: Signature = signature.parse()?;
+
+        let recovered = recover_typed_data(domain, message, &signature)?;
+
+        if recovered != account {
+            return Err(TypedDataError::WrongSigner {
+                expected: account,
+                recovered,
+            });
+        }
+
+        Ok(signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::signers::{LocalWallet, Signer};
+
+    fn domain() -> EIP712Domain {
+        EIP712Domain {
+            name: Some("GameToken".to_string()),
+            version: Some("1".to_string()),
+            chain_id: Some(42161.into()),
+            verifying_contract: Some(Address::repeat_byte(0xcc)),
+            salt: None,
+        }
+    }
+
+    fn permit(owner: Address) -> Permit {
+        Permit {
+            owner,
+            spender: Address::repeat_byte(0x5e),
+            value: U256::exp10(18),
+            nonce: 0.into(),
+            deadline: U256::MAX,
+        }
+    }
+
+    #[test]
+    fn builds_types_from_struct() {
+        let x = typed_data(domain(), &permit(Address::zero())).unwrap();
+
+        assert_eq!(x.primary_type, "Permit");
+        assert_eq!(x.types["EIP712Domain"].len(), 4);
+
+        let names: Vec<_> = x.types["Permit"]
+            .iter()
+            .map(|x| format!("{} {}", x.r#type, x.name))
+            .collect();
+
+        assert_eq!(
+            names,
+            [
+                "address owner",
+                "address spender",
+                "uint256 value",
+                "uint256 nonce",
+                "uint256 deadline"
+            ]
+        );
+    }
+
+    #[test]
+    fn recovers_signer() {
+        let wallet: LocalWallet =
+            "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+                .parse()
+                .unwrap();
+
+        let message = permit(wallet.address());
+
+        let hash = typed_data(domain(), &message)
+            .unwrap()
+            .encode_eip712()
+            .unwrap();
+
+        let signature = wallet.sign_hash(H256(hash)).unwrap();
+
+        let recovered = recover_typed_data(domain(), &message, &signature).unwrap();
+
+        assert_eq!(recovered, wallet.address());
+
+        // a different domain is a different message
+        let other = EIP712Domain {
+            chain_id: Some(1.into()),
+            ..domain()
+        };
+
+        let recovered = recover_typed_data(other, &message, &signature).unwrap();
+
+        assert_ne!(recovered, wallet.address());
+    }
+}
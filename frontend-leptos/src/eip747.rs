@@ -0,0 +1,355 @@
+//! <https://eips.ethereum.org/EIPS/eip-747>
+//!
+//! Ask the wallet to show one of our tokens. Every wallet does this a little differently and many don't support it
+//! at all, so the result is a [`WatchAssetOutcome`] instead of an error whenever possible.
+use crate::bindings::NftReader;
+use crate::eip1193::{EIP1193Provider, ProviderRpcError, ProviderRpcErrorCode};
+use crate::transport::{Transport, TransportError};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ethers::contract::abigen;
+use ethers::types::{Address, U256};
+use leptos::logging::warn;
+use serde::{Deserialize, Serialize};
+
+abigen!(
+    Erc20Metadata,
+    r#"[
+        function symbol() external view returns (string)
+        function decimals() external view returns (uint8)
+    ]"#
+);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "options")]
+pub enum WatchAsset {
+    #[serde(rename = "ERC20", rename_all = "camelCase")]
+    Erc20 {
+        address: Address,
+        symbol: String,
+        decimals: u8,
+    },
+    /// This isn't in the EIP yet. Wallets that only know ERC721 and ERC1155 reject it as invalid params.
+    #[serde(rename = "ERC6909", rename_all = "camelCase")]
+    Erc6909 {
+        address: Address,
+        /// decimal string like ERC1155 uses
+        token_id: String,
+        name: String,
+        symbol: String,
+        decimals: u8,
+        /// the `image` from the `tokenURI` metadata. left out if the metadata couldn't be read
+        #[serde(skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+    },
+}
+
+/// The part of the `tokenURI` json that wallets want.
+#[derive(Deserialize)]
+struct TokenMetadata {
+    image: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchAssetOutcome {
+    Added,
+    /// the user closed the prompt
+    Declined,
+    /// the wallet doesn't have `wallet_watchAsset` or doesn't know this type of asset
+    Unsupported,
+}
+
+impl WatchAsset {
+    /// Read the symbol and decimals of an ERC-20 from the chain.
+    pub async fn erc20(
+        provider: &EIP1193Provider,
+        address: Address,
+    ) -> Result<Self, ProviderRpcError> {
+        let symbol: erc_20_metadata::SymbolReturn = provider
+            .call_contract(address, erc_20_metadata::SymbolCall)
+            .await?;
+
+        let decimals: erc_20_metadata::DecimalsReturn = provider
+            .call_contract(address, erc_20_metadata::DecimalsCall)
+            .await?;
+
+        Ok(Self::Erc20 {
+            address,
+            symbol: symbol.0,
+            decimals: decimals.0,
+        })
+    }
+
+    /// Read the metadata for one id of the dice NFT from the chain.
+    pub async fn erc6909<T: Transport>(
+        nft: &NftReader<T>,
+        id: U256,
+    ) -> Result<Self, TransportError> {
+        let name = nft.name(id).await?;
+        let symbol = nft.symbol(id).await?;
+        let decimals = nft.decimals(id).await?;
+        let token_uri = nft.token_uri(id).await?;
+
+        // the image is optional. a broken metadata server shouldn't stop the token from being added
+        let image = match token_image(&token_uri).await {
+            Ok(x) => x,
+            Err(err) => {
+                warn!("failed reading the metadata for {}: {}", token_uri, err);
+                None
+            }
+        };
+
+        Ok(Self::Erc6909 {
+            address: nft.address(),
+            token_id: id.to_string(),
+            name,
+            symbol,
+            decimals,
+            image,
+        })
+    }
+}
+
+/// Read the metadata json at `tokenURI` and return its `image`.
+///
+/// Inline `data:application/json` uris are decoded here. `http(s)` is fetched. Anything else (like `ipfs://`) needs
+/// a gateway that we don't have, so it has no image.
+async fn token_image(token_uri: &str) -> Result<Option<String>, TransportError> {
+    let metadata: TokenMetadata = if let Some(json) = data_uri_json(token_uri)? {
+        serde_json::from_slice(&json)
+            .map_err(|err| TransportError::InvalidResponse(err.to_string()))?
+    } else if token_uri.starts_with("https://") || token_uri.starts_with("http://") {
+        reqwest::get(token_uri)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?
+    } else {
+        return Ok(None);
+    };
+
+    Ok(metadata.image.filter(|x| !x.is_empty()))
+}
+
+/// The bytes of a `data:application/json` uri. None if it is some other kind of uri.
+fn data_uri_json(uri: &str) -> Result<Option<Vec<u8>>, TransportError> {
+    let Some(rest) = uri
+        .get(..5)
+        .filter(|x| x.eq_ignore_ascii_case("data:"))
+        .map(|_| &uri[5..])
+    else {
+        return Ok(None);
+    };
+
+    let invalid = || TransportError::InvalidResponse(format!("invalid metadata uri: {}", uri));
+
+    let (header, payload) = rest.split_once(',').ok_or_else(invalid)?;
+
+    let mut params = header.split(';');
+
+    if !params
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .eq_ignore_ascii_case("application/json")
+    {
+        return Err(invalid());
+    }
+
+    if params.any(|x| x.trim().eq_ignore_ascii_case("base64")) {
+        BASE64
+            .decode(payload.trim())
+            .map(Some)
+            .map_err(|_| invalid())
+    } else {
+        Ok(Some(
+            percent_encoding::percent_decode_str(payload).collect(),
+        ))
+    }
+}
+
+impl EIP1193Provider {
+    /// `wallet_watchAsset`
+    pub async fn watch_asset(
+        &self,
+        asset: &WatchAsset,
+    ) -> Result<WatchAssetOutcome, ProviderRpcError> {
+        // unlike most methods, the params are an object instead of an array
+        match self.request::<_, bool>("wallet_watchAsset", asset).await {
+            Ok(true) => Ok(WatchAssetOutcome::Added),
+            Ok(false) => Ok(WatchAssetOutcome::Declined),
+            Err(err) if err.is_user_rejected() => Ok(WatchAssetOutcome::Declined),
+            Err(err) => match err.code() {
+                Some(
+                    ProviderRpcErrorCode::UnsupportedMethod
+                    | ProviderRpcErrorCode::MethodNotFound
+                    | ProviderRpcErrorCode::MethodNotSupported
+                    | ProviderRpcErrorCode::InvalidParams,
+                ) => Ok(WatchAssetOutcome::Unsupported),
+                _ => Err(err),
+            },
+        }
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+    use ethers::abi::AbiEncode;
+    use ethers::types::Bytes;
+    use serde_json::json;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn token() -> Address {
+        "0x0000000000000000000000000000000000000d1e"
+            .parse()
+            .unwrap()
+    }
+
+    fn wallet() -> MockProvider {
+        MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+        }))
+    }
+
+    #[wasm_bindgen_test]
+    async fn watches_erc20() {
+        let mock = wallet();
+
+        let symbol = erc_20_metadata::SymbolReturn("GAME".to_string()).encode();
+        let decimals = erc_20_metadata::DecimalsReturn(18).encode();
+
+        mock.respond_once("eth_call", Bytes::from(symbol));
+        mock.respond_once("eth_call", Bytes::from(decimals));
+        mock.respond("wallet_watchAsset", true);
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        let asset = WatchAsset::erc20(&provider, token()).await.unwrap();
+
+        assert_eq!(
+            asset,
+            WatchAsset::Erc20 {
+                address: token(),
+                symbol: "GAME".to_string(),
+                decimals: 18,
+            }
+        );
+
+        let outcome = provider.watch_asset(&asset).await.unwrap();
+
+        assert_eq!(outcome, WatchAssetOutcome::Added);
+
+        assert_eq!(
+            mock.requests_for("wallet_watchAsset"),
+            vec![json!({
+                "type": "ERC20",
+                "options": {
+                    "address": token(),
+                    "symbol": "GAME",
+                    "decimals": 18,
+                },
+            })]
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn reads_erc6909_metadata_from_the_nft() {
+        use crate::public_client::PublicClient;
+
+        let mock = wallet();
+
+        mock.respond_once("eth_call", Bytes::from(("Red".to_string(),).encode()));
+        mock.respond_once("eth_call", Bytes::from(("GD🟥".to_string(),).encode()));
+        mock.respond_once("eth_call", Bytes::from((0u8,).encode()));
+
+        // tokenURI is the metadata, not the image
+        let metadata = json!({ "name": "Red", "image": "https://example.com/1.svg" });
+        let token_uri = format!(
+            "data:application/json;base64,{}",
+            BASE64.encode(metadata.to_string())
+        );
+
+        mock.respond_once("eth_call", Bytes::from((token_uri,).encode()));
+
+        let client = PublicClient::new(EIP1193Provider::new(mock.inner()).unwrap());
+
+        let nft = NftReader::new(client, token());
+
+        let asset = WatchAsset::erc6909(&nft, 1.into()).await.unwrap();
+
+        assert_eq!(
+            asset,
+            WatchAsset::Erc6909 {
+                address: token(),
+                token_id: "1".to_string(),
+                name: "Red".to_string(),
+                symbol: "GD🟥".to_string(),
+                decimals: 0,
+                image: Some("https://example.com/1.svg".to_string()),
+            }
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn leaves_out_images_it_cant_read() {
+        assert_eq!(token_image("ipfs://metadata/1").await.unwrap(), None);
+
+        assert_eq!(
+            token_image("data:application/json,%7B%22image%22%3A%22ipfs%3A%2F%2F1%22%7D")
+                .await
+                .unwrap(),
+            Some("ipfs://1".to_string())
+        );
+
+        assert!(token_image("data:text/html,<script></script>")
+            .await
+            .is_err());
+        assert!(token_image("data:application/json;base64,!!")
+            .await
+            .is_err());
+
+        let asset = WatchAsset::Erc6909 {
+            address: token(),
+            token_id: "1".to_string(),
+            name: "Red".to_string(),
+            symbol: "GD🟥".to_string(),
+            decimals: 0,
+            image: None,
+        };
+
+        let json = serde_json::to_value(asset).unwrap();
+
+        assert_eq!(json["options"].get("image"), None);
+    }
+
+    #[wasm_bindgen_test]
+    async fn unsupported_wallet() {
+        let mock = wallet();
+
+        let provider = EIP1193Provider::new(mock.inner()).unwrap();
+
+        let asset = WatchAsset::Erc6909 {
+            address: token(),
+            token_id: "1".to_string(),
+            name: "Red".to_string(),
+            symbol: "GD🟥".to_string(),
+            decimals: 0,
+            image: None,
+        };
+
+        // the mock rejects anything unscripted with 4200
+        let outcome = provider.watch_asset(&asset).await.unwrap();
+        assert_eq!(outcome, WatchAssetOutcome::Unsupported);
+
+        mock.reject_once("wallet_watchAsset", 4001, "user rejected");
+        let outcome = provider.watch_asset(&asset).await.unwrap();
+        assert_eq!(outcome, WatchAssetOutcome::Declined);
+
+        mock.reject_once("wallet_watchAsset", -32603, "internal error");
+        assert!(provider.watch_asset(&asset).await.is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Typed events from the game and nft contracts.
+//!
+//! Logs from `eth_getLogs` and `eth_subscribe` all decode through [`EventLog::decode`]. Anyone can emit a log with our
+//! topics, so logs are only trusted from the addresses that we expect.
+use crate::bindings::game::{
+    AldersonDiceGameV0Events, ChosenDiceFilter, FeesFilter, OwnershipHandoverCanceledFilter,
+    OwnershipHandoverRequestedFilter, OwnershipTransferredFilter, SetMintDevFeeFilter,
+    SetMintPrizeFeeFilter, SkirmishBagsFilter, SkirmishColorFilter, SkirmishPlayersFilter,
+    SponsoredFilter,
+};
+use crate::bindings::nft::{
+    AldersonDiceNFTEvents, ApprovalFilter, OperatorSetFilter, TransferFilter, UpgradeFilter,
+};
+use ethers::abi::RawLog;
+use ethers::contract::EthLogDecode;
+use ethers::types::{Address, Log, H256};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiceEvent {
+    // game
+    ChosenDice(ChosenDiceFilter),
+    Fees(FeesFilter),
+    Sponsored(SponsoredFilter),
+    SkirmishBags(SkirmishBagsFilter),
+    SkirmishColor(SkirmishColorFilter),
+    SkirmishPlayers(SkirmishPlayersFilter),
+    SetMintDevFee(SetMintDevFeeFilter),
+    SetMintPrizeFee(SetMintPrizeFeeFilter),
+    OwnershipTransferred(OwnershipTransferredFilter),
+    OwnershipHandoverRequested(OwnershipHandoverRequestedFilter),
+    OwnershipHandoverCanceled(OwnershipHandoverCanceledFilter),
+    // nft
+    Transfer(TransferFilter),
+    Approval(ApprovalFilter),
+    OperatorSet(OperatorSetFilter),
+    Upgrade(UpgradeFilter),
+}
+
+impl From<AldersonDiceGameV0Events> for DiceEvent {
+    fn from(x: AldersonDiceGameV0Events) -> Self {
+        use AldersonDiceGameV0Events as E;
+
+        match x {
+            E::ChosenDiceFilter(x) => Self::ChosenDice(x),
+            E::FeesFilter(x) => Self::Fees(x),
+            E::SponsoredFilter(x) => Self::Sponsored(x),
+            E::SkirmishBagsFilter(x) => Self::SkirmishBags(x),
+            E::SkirmishColorFilter(x) => Self::SkirmishColor(x),
+            E::SkirmishPlayersFilter(x) => Self::SkirmishPlayers(x),
+            E::SetMintDevFeeFilter(x) => Self::SetMintDevFee(x),
+            E::SetMintPrizeFeeFilter(x) => Self::SetMintPrizeFee(x),
+            E::OwnershipTransferredFilter(x) => Self::OwnershipTransferred(x),
+            E::OwnershipHandoverRequestedFilter(x) => Self::OwnershipHandoverRequested(x),
+            E::OwnershipHandoverCanceledFilter(x) => Self::OwnershipHandoverCanceled(x),
+        }
+    }
+}
+
+impl From<AldersonDiceNFTEvents> for DiceEvent {
+    fn from(x: AldersonDiceNFTEvents) -> Self {
+        use AldersonDiceNFTEvents as E;
+
+        match x {
+            E::TransferFilter(x) => Self::Transfer(x),
+            E::ApprovalFilter(x) => Self::Approval(x),
+            E::OperatorSetFilter(x) => Self::OperatorSet(x),
+            E::UpgradeFilter(x) => Self::Upgrade(x),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EventError {
+    /// the first topic doesn't match any event in our abis
+    Unknown(Option<H256>),
+    /// the log couldn't be turned into an ethers `Log`
+    InvalidLog(String),
+    /// the log didn't come from the contract that emits its event
+    UnexpectedAddress(Address),
+}
+
+impl std::fmt::Display for EventError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown(Some(x)) => write!(f, "unknown event: {:?}", x),
+            Self::Unknown(None) => write!(f, "unknown anonymous event"),
+            Self::InvalidLog(x) => write!(f, "invalid log: {}", x),
+            Self::UnexpectedAddress(x) => write!(f, "log from unexpected address: {:?}", x),
+        }
+    }
+}
+
+impl std::error::Error for EventError {}
+
+/// An event and where it happened. The location is None for pending logs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventLog {
+    pub event: DiceEvent,
+    pub address: Address,
+    pub block_number: Option<u64>,
+    pub block_hash: Option<H256>,
+    pub transaction_hash: Option<H256>,
+    pub log_index: Option<u64>,
+    /// true if a reorg dropped a log that was already delivered
+    pub removed: bool,
+}
+
+impl EventLog {
+    /// Game events are only accepted from `game` and nft events only from `nft`.
+    pub fn decode(log: &Log, game: Address, nft: Address) -> Result<Self, EventError> {
+        let raw = RawLog::from(log.clone());
+
+        // none of the events share a name, so the topic says which contract should have emitted it
+        let (event, expected): (DiceEvent, _) = match AldersonDiceGameV0Events::decode_log(&raw) {
+            Ok(x) => (x.into(), game),
+            Err(_) => {
+                let x = AldersonDiceNFTEvents::decode_log(&raw)
+                    .map_err(|_| EventError::Unknown(log.topics.first().copied()))?;
+
+                (x.into(), nft)
+            }
+        };
+
+        if log.address != expected {
+            return Err(EventError::UnexpectedAddress(log.address));
+        }
+
+        Ok(Self {
+            event,
+            address: log.address,
+            block_number: log.block_number.map(|x| x.as_u64()),
+            block_hash: log.block_hash,
+            transaction_hash: log.transaction_hash,
+            log_index: log.log_index.map(|x| x.as_u64()),
+            removed: log.removed.unwrap_or(false),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::{encode, Tokenizable};
+    use ethers::contract::EthEvent;
+    use ethers::types::{Bytes, U256};
+
+    const GAME: Address = Address::repeat_byte(0x22);
+    const NFT: Address = Address::repeat_byte(0x33);
+
+    fn log(address: Address, topics: Vec<H256>, data: Vec<u8>) -> Log {
+        Log {
+            address,
+            topics,
+            data: Bytes::from(data),
+            block_number: Some(16.into()),
+            transaction_hash: Some(H256::repeat_byte(0xab)),
+            log_index: Some(3.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn decodes_game_and_nft_events() {
+        let player = Address::repeat_byte(0x01);
+        let dice = [U256::from(7); 10];
+
+        let chosen = log(
+            GAME,
+            vec![ChosenDiceFilter::signature(), player.into()],
+            encode(&[dice.into_token()]),
+        );
+
+        let x = EventLog::decode(&chosen, GAME, NFT).unwrap();
+
+        assert_eq!(
+            x.event,
+            DiceEvent::ChosenDice(ChosenDiceFilter { player, dice })
+        );
+        assert_eq!(x.block_number, Some(16));
+        assert_eq!(x.transaction_hash, Some(H256::repeat_byte(0xab)));
+        assert_eq!(x.log_index, Some(3));
+        assert!(!x.removed);
+
+        let to = Address::repeat_byte(0x02);
+
+        let transfer = log(
+            NFT,
+            vec![
+                TransferFilter::signature(),
+                Address::zero().into(),
+                to.into(),
+                H256::from_low_u64_be(1),
+            ],
+            encode(&[player.into_token(), U256::from(5).into_token()]),
+        );
+
+        assert_eq!(
+            EventLog::decode(&transfer, GAME, NFT).unwrap().event,
+            DiceEvent::Transfer(TransferFilter {
+                by: player,
+                from: Address::zero(),
+                to,
+                id: 1.into(),
+                amount: 5.into(),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_events() {
+        let topic = H256::repeat_byte(0xee);
+
+        assert_eq!(
+            EventLog::decode(&log(GAME, vec![topic], vec![]), GAME, NFT),
+            Err(EventError::Unknown(Some(topic)))
+        );
+    }
+
+    #[test]
+    fn rejects_logs_from_other_addresses() {
+        let fee = encode(&[U256::from(100).into_token()]);
+
+        let spoofed = Address::repeat_byte(0xee);
+
+        assert_eq!(
+            EventLog::decode(
+                &log(spoofed, vec![SetMintDevFeeFilter::signature()], fee.clone()),
+                GAME,
+                NFT
+            ),
+            Err(EventError::UnexpectedAddress(spoofed))
+        );
+
+        // a game event from the nft is just as wrong
+        assert_eq!(
+            EventLog::decode(
+                &log(NFT, vec![SetMintDevFeeFilter::signature()], fee),
+                GAME,
+                NFT
+            ),
+            Err(EventError::UnexpectedAddress(NFT))
+        );
+    }
+}
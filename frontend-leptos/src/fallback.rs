@@ -0,0 +1,293 @@
+//! A transport that spreads requests over several RPCs.
+//!
+//! Every request goes to the best endpoint first. Endpoints are ranked by how fast they answer and how often they
+//! fail. After a few errors in a row an endpoint is skipped for a while and then given another chance.
+use crate::transport::{HttpTransport, Transport, TransportError};
+use serde::{de::DeserializeOwned, Serialize};
+use std::cell::RefCell;
+
+/// how much a new latency sample moves the average
+const LATENCY_WEIGHT: f64 = 0.2;
+
+/// errors in a row before an endpoint is skipped
+const MAX_CONSECUTIVE_ERRORS: u32 = 3;
+
+/// how long an unhealthy endpoint is skipped
+const UNHEALTHY_MS: f64 = 30_000.0;
+
+/// a failed request costs much more than a slow one, so errors count heavily against the latency
+const ERROR_PENALTY: f64 = 10.0;
+
+/// How an endpoint has been doing.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EndpointHealth {
+    /// moving average. None until the first response
+    pub latency_ms: Option<f64>,
+    pub requests: u64,
+    pub errors: u64,
+    consecutive_errors: u32,
+    /// the endpoint is skipped until this time
+    unhealthy_until: Option<f64>,
+}
+
+impl EndpointHealth {
+    fn record_success(&mut self, latency_ms: f64) {
+        self.requests += 1;
+        self.consecutive_errors = 0;
+        self.unhealthy_until = None;
+
+        self.latency_ms = Some(match self.latency_ms {
+            Some(x) => x + LATENCY_WEIGHT * (latency_ms - x),
+            None => latency_ms,
+        });
+    }
+
+    fn record_error(&mut self, now: f64) {
+        self.requests += 1;
+        self.errors += 1;
+        self.consecutive_errors += 1;
+
+        if self.consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+            self.unhealthy_until = Some(now + UNHEALTHY_MS);
+        }
+    }
+
+    pub fn is_healthy(&self, now: f64) -> bool {
+        self.unhealthy_until.map(|x| now >= x).unwrap_or(true)
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.requests as f64
+        }
+    }
+
+    /// Lower is better. Endpoints that haven't answered yet go first so that they get measured.
+    fn score(&self) -> f64 {
+        self.latency_ms.unwrap_or(0.0) * (1.0 + ERROR_PENALTY * self.error_rate())
+    }
+}
+
+/// The order to try endpoints in. Unhealthy endpoints are left out unless all of them are unhealthy.
+fn rank(health: &[EndpointHealth], now: f64) -> Vec<usize> {
+    let (mut healthy, mut unhealthy): (Vec<usize>, Vec<usize>) =
+        (0..health.len()).partition(|&i| health[i].is_healthy(now));
+
+    if healthy.is_empty() {
+        // the one that went bad first is the most likely to have recovered
+        unhealthy.sort_by(|&a, &b| {
+            health[a]
+                .unhealthy_until
+                .partial_cmp(&health[b].unhealthy_until)
+                .expect("times are never NaN")
+        });
+
+        return unhealthy;
+    }
+
+    // sort is stable, so ties keep the configured order
+    healthy.sort_by(|&a, &b| {
+        health[a]
+            .score()
+            .partial_cmp(&health[b].score())
+            .expect("scores are never NaN")
+    });
+
+    healthy
+}
+
+/// milliseconds since the unix epoch
+fn now_ms() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        js_sys::Date::now()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_secs_f64()
+            * 1000.0
+    }
+}
+
+/// One row of the diagnostics panel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EndpointStatus {
+    pub url: String,
+    pub healthy: bool,
+    pub health: EndpointHealth,
+}
+
+#[derive(Debug)]
+struct Endpoint<T> {
+    url: String,
+    transport: T,
+    health: RefCell<EndpointHealth>,
+}
+
+#[derive(Debug)]
+pub struct FallbackTransport<T = HttpTransport> {
+    endpoints: Vec<Endpoint<T>>,
+}
+
+impl FallbackTransport<HttpTransport> {
+    pub fn http(urls: &[String]) -> Self {
+        Self::new(urls.iter().map(|x| (x.clone(), HttpTransport::new(x))))
+    }
+}
+
+impl<T: Transport> FallbackTransport<T> {
+    /// `(url, transport)` pairs. The url is only used for logs and diagnostics.
+    pub fn new(endpoints: impl IntoIterator<Item = (String, T)>) -> Self {
+        let endpoints = endpoints
+            .into_iter()
+            .map(|(url, transport)| Endpoint {
+                url,
+                transport,
+                health: Default::default(),
+            })
+            .collect();
+
+        Self { endpoints }
+    }
+
+    fn ranked(&self, now: f64) -> Vec<usize> {
+        let health = self
+            .endpoints
+            .iter()
+            .map(|x| x.health.borrow().clone())
+            .collect::<Vec<_>>();
+
+        rank(&health, now)
+    }
+
+    /// Every endpoint in the order that they will be tried.
+    pub fn status(&self) -> Vec<EndpointStatus> {
+        let now = now_ms();
+
+        let mut ranked = self.ranked(now);
+
+        // include the ones that are being skipped at the end
+        let skipped = (0..self.endpoints.len())
+            .filter(|i| !ranked.contains(i))
+            .collect::<Vec<_>>();
+
+        ranked.extend(skipped);
+
+        ranked
+            .into_iter()
+            .map(|i| {
+                let endpoint = &self.endpoints[i];
+                let health = endpoint.health.borrow().clone();
+
+                EndpointStatus {
+                    url: endpoint.url.clone(),
+                    healthy: health.is_healthy(now),
+                    health,
+                }
+            })
+            .collect()
+    }
+}
+
+impl<T: Transport> Transport for FallbackTransport<T> {
+    async fn request<P: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &P,
+    ) -> Result<R, TransportError> {
+        let mut last_err = None;
+
+        for i in self.ranked(now_ms()) {
+            let endpoint = &self.endpoints[i];
+
+            let start = now_ms();
+
+            match endpoint.transport.request(method, params).await {
+                Ok(x) => {
+                    endpoint
+                        .health
+                        .borrow_mut()
+                        .record_success(now_ms() - start);
+                    return Ok(x);
+                }
+                Err(err) if err.is_retryable() => {
+                    leptos::logging::warn!("{} failed {}: {}", endpoint.url, method, err);
+
+                    endpoint.health.borrow_mut().record_error(now_ms());
+
+                    last_err = Some(err);
+                }
+                Err(err) => {
+                    // the node answered. another node would say the same thing
+                    endpoint
+                        .health
+                        .borrow_mut()
+                        .record_success(now_ms() - start);
+                    return Err(err);
+                }
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| TransportError::InvalidRequest("no rpc urls".to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(latency_ms: f64, requests: u64, errors: u64) -> EndpointHealth {
+        EndpointHealth {
+            latency_ms: Some(latency_ms),
+            requests,
+            errors,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ranks_by_latency_and_errors() {
+        let all = [
+            health(200.0, 10, 0),
+            health(50.0, 10, 5),
+            health(100.0, 10, 0),
+            EndpointHealth::default(),
+        ];
+
+        // unmeasured first. then 100ms. then 200ms beats 50ms with half of its requests failing
+        assert_eq!(rank(&all, 0.0), vec![3, 2, 0, 1]);
+    }
+
+    #[test]
+    fn skips_unhealthy_endpoints_until_they_cool_down() {
+        let mut all = [health(50.0, 1, 0), health(100.0, 1, 0)];
+
+        for _ in 0..MAX_CONSECUTIVE_ERRORS {
+            all[0].record_error(1_000.0);
+        }
+
+        assert!(!all[0].is_healthy(1_000.0));
+        assert_eq!(rank(&all, 1_000.0), vec![1]);
+
+        // everything is unhealthy. try them anyway
+        for _ in 0..MAX_CONSECUTIVE_ERRORS {
+            all[1].record_error(2_000.0);
+        }
+
+        assert_eq!(rank(&all, 2_000.0), vec![0, 1]);
+
+        // the first one gets another chance after the cooldown
+        assert_eq!(rank(&all, 1_000.0 + UNHEALTHY_MS), vec![0]);
+
+        all[0].record_success(80.0);
+
+        assert!(all[0].is_healthy(0.0));
+        assert_eq!(all[0].latency_ms, Some(56.0));
+    }
+}
@@ -0,0 +1,486 @@
+//! Validate the icons that wallets send us before we render them.
+//!
+//! EIP-6963 icons are data URIs from third-party extensions. Only a few image types are allowed, big icons are
+//! refused, and SVGs are rebuilt without scripts, event handlers, or references to anything outside the icon.
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+/// the most bytes an icon may have after decoding. wallet icons are tiny
+pub const MAX_ICON_BYTES: usize = 64 * 1024;
+
+/// elements that are dropped along with everything inside them
+const FORBIDDEN_ELEMENTS: [&str; 8] = [
+    "script",
+    "style",
+    "foreignobject",
+    "iframe",
+    "object",
+    "embed",
+    "audio",
+    "video",
+];
+
+/// attributes that load something. only references to an id inside the same svg are kept
+const REF_ATTRIBUTES: [&str; 4] = ["href", "xlink:href", "src", "xml:base"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IconError {
+    NotDataUri,
+    UnsupportedType(String),
+    TooLarge(usize),
+    InvalidEncoding,
+    /// the bytes don't look like the type that the uri says they are
+    WrongContent,
+}
+
+impl std::fmt::Display for IconError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotDataUri => write!(f, "icon is not a data uri"),
+            Self::UnsupportedType(x) => write!(f, "unsupported icon type: {}", x),
+            Self::TooLarge(x) => write!(f, "icon is too large: {} bytes", x),
+            Self::InvalidEncoding => write!(f, "icon is not encoded correctly"),
+            Self::WrongContent => write!(f, "icon does not match its type"),
+        }
+    }
+}
+
+impl std::error::Error for IconError {}
+
+/// Check a wallet's icon and return a data URI that is safe to put in an `img`.
+pub fn sanitize_icon(uri: &str) -> Result<String, IconError> {
+    // base64 is 4/3 the size of the bytes. don't bother decoding anything that is obviously too big
+    if uri.len() > MAX_ICON_BYTES * 2 {
+        return Err(IconError::TooLarge(uri.len()));
+    }
+
+    let rest = uri
+        .get(..5)
+        .filter(|x| x.eq_ignore_ascii_case("data:"))
+        .map(|_| &uri[5..])
+        .ok_or(IconError::NotDataUri)?;
+
+    let (header, payload) = rest.split_once(',').ok_or(IconError::NotDataUri)?;
+
+    let mut params = header.split(';');
+
+    let media_type = params
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    let is_base64 = params.any(|x| x.trim().eq_ignore_ascii_case("base64"));
+
+    let bytes = if is_base64 {
+        BASE64
+            .decode(payload.trim())
+            .map_err(|_| IconError::InvalidEncoding)?
+    } else {
+        percent_encoding::percent_decode_str(payload).collect()
+    };
+
+    if bytes.len() > MAX_ICON_BYTES {
+        return Err(IconError::TooLarge(bytes.len()));
+    }
+
+    let bytes = match media_type.as_str() {
+        "image/svg+xml" => {
+            let svg = String::from_utf8(bytes).map_err(|_| IconError::InvalidEncoding)?;
+
+            sanitize_svg(&svg)?.into_bytes()
+        }
+        "image/png" if bytes.starts_with(b"\x89PNG\r\n\x1a\n") => bytes,
+        "image/jpeg" if bytes.starts_with(&[0xff, 0xd8, 0xff]) => bytes,
+        "image/webp" if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" => {
+            bytes
+        }
+        "image/png" | "image/jpeg" | "image/webp" => return Err(IconError::WrongContent),
+        _ => return Err(IconError::UnsupportedType(media_type)),
+    };
+
+    Ok(format!(
+        "data:{};base64,{}",
+        media_type,
+        BASE64.encode(bytes)
+    ))
+}
+
+/// Rebuild an svg with only the parts that can't run code or load anything.
+///
+/// This isn't a full XML parser. Anything it doesn't understand is dropped.
+pub fn sanitize_svg(svg: &str) -> Result<String, IconError> {
+    let mut out = String::with_capacity(svg.len());
+
+    let mut rest = svg;
+
+    let mut saw_svg = false;
+
+    while let Some(start) = rest.find('<') {
+        out.push_str(&escape_text(&rest[..start]));
+
+        rest = &rest[start..];
+
+        // comments, doctypes (entities!), cdata, and processing instructions are all dropped
+        if let Some(x) = rest.strip_prefix("<!--") {
+            rest = x.find("-->").map(|i| &x[i + 3..]).unwrap_or("");
+            continue;
+        }
+
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = skip_markup(rest);
+            continue;
+        }
+
+        let Some(end) = tag_end(rest) else {
+            // an unclosed tag. drop the rest
+            rest = "";
+            break;
+        };
+
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+
+            if is_allowed_name(name) {
+                out.push_str("</");
+                out.push_str(name);
+                out.push('>');
+            }
+            continue;
+        }
+
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+
+        let name_end = tag.find(|c: char| c.is_whitespace()).unwrap_or(tag.len());
+
+        let (name, attributes) = tag.split_at(name_end);
+
+        let lower_name = name.to_ascii_lowercase();
+
+        if FORBIDDEN_ELEMENTS.contains(&lower_name.as_str()) || !is_allowed_name(name) {
+            if !self_closing {
+                rest = skip_element(rest, &lower_name);
+            }
+            continue;
+        }
+
+        if lower_name == "svg" {
+            saw_svg = true;
+        }
+
+        out.push('<');
+        out.push_str(name);
+
+        for (key, value) in parse_attributes(attributes) {
+            if is_allowed_attribute(&key, &value) {
+                out.push(' ');
+                out.push_str(&key);
+                out.push_str("=\"");
+                out.push_str(&escape_attribute(&value));
+                out.push('"');
+            }
+        }
+
+        if self_closing {
+            out.push('/');
+        }
+        out.push('>');
+    }
+
+    out.push_str(&escape_text(rest));
+
+    if !saw_svg {
+        return Err(IconError::WrongContent);
+    }
+
+    Ok(out)
+}
+
+/// the index of the `>` that ends the tag at the start of `x`. quotes are respected
+fn tag_end(x: &str) -> Option<usize> {
+    let mut quote = None;
+
+    for (i, c) in x.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// skip a `<!...>` or `<?...?>`. doctypes can have nested brackets for their entities
+fn skip_markup(x: &str) -> &str {
+    let mut depth = 0;
+
+    for (i, c) in x.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return &x[i + 1..];
+                }
+            }
+            _ => {}
+        }
+    }
+
+    ""
+}
+
+/// skip everything up to and including the close tag for `name`
+fn skip_element<'a>(x: &'a str, name: &str) -> &'a str {
+    let lower = x.to_ascii_lowercase();
+
+    let close = format!("</{}", name);
+
+    match lower.find(&close) {
+        Some(i) => {
+            let after = &x[i..];
+            after.find('>').map(|j| &after[j + 1..]).unwrap_or("")
+        }
+        None => "",
+    }
+}
+
+fn is_allowed_name(x: &str) -> bool {
+    !x.is_empty()
+        && x.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+}
+
+fn parse_attributes(mut x: &str) -> Vec<(String, String)> {
+    let mut attributes = vec![];
+
+    loop {
+        x = x.trim_start();
+
+        let key_end = x
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(x.len());
+
+        if key_end == 0 {
+            break;
+        }
+
+        let key = x[..key_end].to_string();
+
+        x = x[key_end..].trim_start();
+
+        let Some(after_eq) = x.strip_prefix('=') else {
+            // a key without a value
+            attributes.push((key, String::new()));
+            continue;
+        };
+
+        x = after_eq.trim_start();
+
+        let value = match x.chars().next() {
+            Some(q @ ('"' | '\'')) => {
+                let end = x[1..].find(q).map(|i| i + 1).unwrap_or(x.len());
+                let value = x[1..end].to_string();
+                x = x.get(end + 1..).unwrap_or("");
+                value
+            }
+            _ => {
+                let end = x.find(char::is_whitespace).unwrap_or(x.len());
+                let value = x[..end].to_string();
+                x = &x[end..];
+                value
+            }
+        };
+
+        attributes.push((key, value));
+    }
+
+    attributes
+}
+
+fn is_allowed_attribute(key: &str, value: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+
+    if !is_allowed_name(&key) || key.starts_with("on") {
+        return false;
+    }
+
+    // ignore whitespace and case so that things like "java\tscript:" are caught too
+    let compact: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase();
+
+    if REF_ATTRIBUTES.contains(&key.as_str()) {
+        return compact.starts_with('#');
+    }
+
+    if compact.contains("javascript:") || compact.contains("expression(") {
+        return false;
+    }
+
+    // things like `fill="url(#gradient)"` are fine. anything else would load from outside the icon
+    compact.match_indices("url(").all(|(i, _)| {
+        compact[i + 4..]
+            .trim_start_matches(['"', '\''])
+            .starts_with('#')
+    })
+}
+
+fn escape_text(x: &str) -> String {
+    x.replace('>', "&gt;")
+}
+
+fn escape_attribute(x: &str) -> String {
+    x.replace('"', "&quot;").replace('<', "&lt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn svg_uri(svg: &str) -> String {
+        format!("data:image/svg+xml;base64,{}", BASE64.encode(svg))
+    }
+
+    fn sanitized(svg: &str) -> String {
+        let uri = sanitize_icon(&svg_uri(svg)).unwrap();
+
+        let payload = uri.split_once(',').unwrap().1;
+
+        String::from_utf8(BASE64.decode(payload).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn keeps_plain_svg() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"><defs><linearGradient id="g"/></defs><rect width="10" height="10" fill="url(#g)"/></svg>"##;
+
+        assert_eq!(sanitized(svg), svg);
+    }
+
+    #[test]
+    fn strips_scripts() {
+        let x = sanitized(
+            r#"<svg><script>alert(1)</script><SCRIPT type="text/javascript">alert(2)</SCRIPT><circle r="1"/></svg>"#,
+        );
+
+        assert_eq!(x, r#"<svg><circle r="1"/></svg>"#);
+    }
+
+    #[test]
+    fn strips_event_handlers() {
+        let x = sanitized(r#"<svg onload="alert(1)"><rect ONCLICK='alert(2)' width="1"/></svg>"#);
+
+        assert_eq!(x, r#"<svg><rect width="1"/></svg>"#);
+    }
+
+    #[test]
+    fn strips_external_refs() {
+        let x = sanitized(
+            r##"<svg><image href="https://evil.example/track.png"/><use xlink:href="https://evil.example/x.svg#a"/><a href="javascript:alert(1)"><rect fill="url(https://evil.example/p)" stroke="url( '#ok' )"/></a><use href="#local"/></svg>"##,
+        );
+
+        assert_eq!(
+            x,
+            r##"<svg><image/><use/><a><rect stroke="url( '#ok' )"/></a><use href="#local"/></svg>"##
+        );
+    }
+
+    #[test]
+    fn strips_foreign_object_and_style() {
+        let x = sanitized(
+            r#"<svg><foreignObject><iframe src="https://evil.example"></iframe></foreignObject><style>@import url(https://evil.example);</style><g style="background:url(https://evil.example)"/></svg>"#,
+        );
+
+        assert_eq!(x, "<svg><g/></svg>");
+    }
+
+    #[test]
+    fn strips_doctype_and_comments() {
+        let x = sanitized(
+            r#"<?xml version="1.0"?><!DOCTYPE svg [<!ENTITY lol "lol">]><!-- <script>alert(1)</script> --><svg>&lol;</svg>"#,
+        );
+
+        assert_eq!(x, "<svg>&lol;</svg>");
+    }
+
+    #[test]
+    fn attribute_breakout_is_escaped() {
+        let x = sanitized(r#"<svg><rect id='a"onmouseover="alert(1)'/></svg>"#);
+
+        assert_eq!(
+            x,
+            r#"<svg><rect id="a&quot;onmouseover=&quot;alert(1)"/></svg>"#
+        );
+    }
+
+    #[test]
+    fn percent_encoded_svg() {
+        let uri = "data:image/svg+xml,%3Csvg%20onload%3D%22alert(1)%22%3E%3C%2Fsvg%3E";
+
+        let x = sanitize_icon(uri).unwrap();
+
+        assert_eq!(
+            x,
+            format!("data:image/svg+xml;base64,{}", BASE64.encode("<svg></svg>"))
+        );
+    }
+
+    #[test]
+    fn rejects_other_types() {
+        assert_eq!(
+            sanitize_icon("data:text/html;base64,PHNjcmlwdD4="),
+            Err(IconError::UnsupportedType("text/html".to_string()))
+        );
+        assert_eq!(
+            sanitize_icon("https://evil.example/icon.png"),
+            Err(IconError::NotDataUri)
+        );
+        assert_eq!(
+            sanitize_icon("javascript:alert(1)"),
+            Err(IconError::NotDataUri)
+        );
+        assert_eq!(
+            sanitize_icon(&svg_uri("<html><body/></html>")),
+            Err(IconError::WrongContent)
+        );
+    }
+
+    #[test]
+    fn checks_raster_content() {
+        let png = BASE64.encode(b"\x89PNG\r\n\x1a\nrest of the png");
+
+        let uri = format!("data:image/png;base64,{}", png);
+
+        assert_eq!(sanitize_icon(&uri), Ok(uri));
+
+        // an svg pretending to be a png
+        let fake = format!(
+            "data:image/png;base64,{}",
+            BASE64.encode("<svg onload=alert(1)>")
+        );
+
+        assert_eq!(sanitize_icon(&fake), Err(IconError::WrongContent));
+
+        assert_eq!(
+            sanitize_icon("data:image/png;base64,not base64!"),
+            Err(IconError::InvalidEncoding)
+        );
+    }
+
+    #[test]
+    fn rejects_large_icons() {
+        let big = format!("<svg>{}</svg>", " ".repeat(MAX_ICON_BYTES));
+
+        assert!(matches!(
+            sanitize_icon(&svg_uri(&big)),
+            Err(IconError::TooLarge(_))
+        ));
+    }
+}
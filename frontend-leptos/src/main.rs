@@ -1,19 +1,49 @@
+pub mod backfill;
+pub mod bindings;
+pub mod block_watcher;
+pub mod chains;
 pub mod eip1193;
+pub mod eip5792;
 pub mod eip6963;
+pub mod eip712;
+pub mod eip747;
+pub mod events;
+pub mod fallback;
+pub mod icon;
+pub mod multicall;
+pub mod public_client;
+pub mod revert;
+pub mod siwe;
+pub mod snapshot;
+pub mod storage;
+pub mod transport;
 pub mod viem;
 
-use derive_more::From;
-use js_sys::{Array, BigInt, Object, Reflect};
+#[cfg(all(test, target_arch = "wasm32"))]
+mod mock_provider;
+
+use bindings::game::{
+    BuyNumDiceCall, ChosenDiceFilter, DieInfo, PriceWithFeesCall, PriceWithFeesReturn,
+    PrizeTokenCall, PrizeTokenReturn,
+};
+use bindings::{GameReader, NftReader};
+use block_watcher::BlockWatcher;
+use eip5792::{BatchCalls, BatchProgress, Call};
+use ethers::contract::{abigen, EthEvent};
+use ethers::types::{Address, Filter, U256};
+use fallback::FallbackTransport;
+use futures::StreamExt;
 use leptos::{logging::log, *};
-use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
-use std::{collections::HashMap, rc::Rc};
-use viem::{ReadAndWriteContract, ReadOnlyContract, ViemPublicClient, ViemWalletClient};
-use wasm_bindgen::{closure::Closure, prelude::wasm_bindgen, JsCast, JsValue};
-use web_sys::window;
+use public_client::PublicClient;
+use snapshot::GameSnapshot;
+use std::rc::Rc;
+use std::time::Duration;
+use transport::{ReadTransport, TransportError};
+use viem::{ViemPublicClient, ViemWalletClient};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
 // TODO: make it easier to switch to dev chain. maybe only if theres a custom param in the url
-const ARBITRUM_CHAIN_ID: &str = "0xa4b1";
+const ARBITRUM_CHAIN_ID: u64 = chains::ARBITRUM.chain_id;
 
 // TODO: get this from the build artifacts
 const NFT_ADDRESS: &str = "0xFFA4DB58Ad08525dFeB232858992047ECab26e95";
@@ -21,6 +51,23 @@ const NFT_ADDRESS: &str = "0xFFA4DB58Ad08525dFeB232858992047ECab26e95";
 // TODO: make this a signal
 const EMIT_MISSED: bool = false;
 
+/// how long to wait for an EIP-6963 announcement before looking at `window.ethereum`
+const LEGACY_PROVIDER_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// how many chosen dice events to show
+const CHOSEN_DICE_FEED_LEN: usize = 10;
+
+/// how often the rpc diagnostics panel refreshes
+const RPC_DIAGNOSTICS_INTERVAL: Duration = Duration::from_secs(2);
+
+// the prize token is a plain ERC-20, so it isn't in our abis
+abigen!(
+    Erc20,
+    r#"[
+        function approve(address spender, uint256 amount) external returns (bool)
+    ]"#
+);
+
 fn main() {
     console_error_panic_hook::set_once();
 
@@ -29,520 +76,364 @@ fn main() {
     });
 }
 
-#[derive(Clone, Debug, From, PartialEq)]
-enum Contract {
-    ReadOnly(ReadOnlyContract),
-    ReadAndWrite(ReadAndWriteContract),
-}
-
-impl Contract {
-    pub async fn read(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        match self {
-            Contract::ReadOnly(contract) => contract.read(fn_name, args, options).await,
-            Contract::ReadAndWrite(contract) => contract.read(fn_name, args, options).await,
-        }
-    }
-
-    pub fn address(&self) -> Option<String> {
-        let inner = self.inner_ref();
-
-        Reflect::get(inner, &"address".into())
-            .expect("failed to get address")
-            .as_string()
-    }
-
-    pub fn inner(&self) -> JsValue {
-        match self {
-            Contract::ReadOnly(contract) => contract.inner.clone(),
-            Contract::ReadAndWrite(contract) => contract.inner(),
-        }
-    }
-
-    pub fn inner_ref(&self) -> &JsValue {
-        match self {
-            Contract::ReadOnly(contract) => &contract.inner,
-            Contract::ReadAndWrite(contract) => contract.inner_ref(),
-        }
-    }
-
-    // pub fn public_client(&self) -> JsValue {
-    //     match self {
-    //         Contract::ReadOnly(contract) => contract.public_client.clone(),
-    //         Contract::ReadAndWrite(contract) => contract.public_client.clone(),
-    //     }
-    // }
-
-    // pub fn wallet_client(&self) -> Option<JsValue> {
-    //     match self {
-    //         Contract::ReadOnly(_) => None,
-    //         Contract::ReadAndWrite(contract) => Some(contract.wallet_client.clone()),
-    //     }
-    // }
-}
-
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
-struct DiceColor {
-    id: usize,
-    name: String,
-    symbol: String,
-    pips: Vec<String>,
-}
-
 #[component]
 fn App() -> impl IntoView {
-    let the_window = window().expect("no global `window` exists");
-
     let x = hello();
     log!("{:?}", x);
 
+    // `?rpc=` in the url overrides the public rpcs. this is how to use a local node
+    let chain_config = chains::ChainConfig::load(chains::ARBITRUM);
+
+    log!(
+        "rpcs from {:?}: {:?}",
+        chain_config.source,
+        chain_config.rpc_urls
+    );
+
+    let rpc = Rc::new(FallbackTransport::http(&chain_config.rpc_urls));
+
     // give the users some data without any wallet connected
-    let defaultPublicClient = ViemPublicClient::new(ARBITRUM_CHAIN_ID.to_string(), None);
+    let defaultPublicClient =
+        ViemPublicClient::new(format!("{:#x}", ARBITRUM_CHAIN_ID), None, rpc.clone());
 
     // TODO: i think these should maybe be moved into their own components
     let (count, set_count) = create_signal(0);
 
-    let (all_providers, set_all_providers) = create_signal(Vec::new());
+    let (all_providers, set_all_providers) =
+        create_signal::<Vec<eip6963::EIP6963ProviderDetail>>(Vec::new());
 
-    // TODO: i want this to be a resource except for the fact that we call set_chain_id inside a callback
-    // TODO: maybe that callback should be rewritten to instead call refetch on this? that doesn't seem right either
     let (selected_provider, set_selected_provider) =
         create_signal::<Option<eip1193::EIP1193Provider>>(None);
 
-    let (chain_id, set_chain_id) = create_signal("".to_string());
-    let (public_client, set_public_client) = create_signal(defaultPublicClient.clone());
-    let (wallet_client, set_wallet_client) = create_signal::<Option<ViemWalletClient>>(None);
+    // true once the user clicks connect. false once they click disconnect
+    let (wallet_enabled, set_wallet_enabled) = create_signal(false);
 
-    let (latest_block_head, set_latest_block_header) =
-        create_signal::<Option<HashMap<String, JsValue>>>(None);
+    let (provider_error, set_provider_error) =
+        create_signal::<Option<eip1193::ProviderRpcError>>(None);
 
-    let latest_block_number = move || {
-        if let Some(block) = latest_block_head() {
-            if let Some(number) = block.get("number") {
-                let number = number.dyn_ref::<BigInt>().expect("timestamp is bigint");
+    // these follow the wallet's chainChanged and accountsChanged events
+    let chain_id =
+        move || selected_provider.with(|x| x.as_ref().and_then(|x| x.chain_id_signal()()));
 
-                let number = format!("{}", number.to_string(10).unwrap());
-
-                Some(number)
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+    let wallet_accounts = move || {
+        selected_provider
+            .with(|x| x.as_ref().map(|x| x.accounts_signal()()))
+            .unwrap_or_default()
     };
 
-    let latest_block_timestamp = move || {
-        if let Some(block) = latest_block_head() {
-            if let Some(timestamp) = block.get("timestamp") {
-                let timestamp = timestamp.dyn_ref::<BigInt>().expect("timestamp is bigint");
-
-                let timestamp = format!("{}", timestamp.to_string(10).unwrap());
-
-                Some(timestamp)
-            } else {
-                None
-            }
-        } else {
-            None
+    // only use the wallet while it is on the chain that our contracts are on and the user hasn't revoked our access
+    let wallet_client = create_memo(move |_| {
+        if !wallet_enabled()
+            || chain_id() != Some(ARBITRUM_CHAIN_ID)
+            || wallet_accounts().is_empty()
+        {
+            return None;
         }
-    };
 
-    let latest_block_hash = move || {
-        if let Some(block) = latest_block_head() {
-            if let Some(x) = block.get("hash") {
-                let x = x.as_string().expect("blockhash is not a string");
-                Some(x)
-            } else {
-                log!("valid keys: {:?}", block.keys());
-                None
-            }
-        } else {
-            None
-        }
-    };
-
-    let nft_contract = move || {
-        let public_inner = public_client.with(|x| x.inner());
-
-        let contract = if let Some(wallet) = wallet_client() {
-            let wallet_inner = wallet.inner();
-
-            let nftContract = nftContract(
-                public_inner.clone(),
-                wallet_inner.clone(),
-                NFT_ADDRESS.to_string(),
-            );
-
-            // TODO: type specific to the game contract
-            let nftContract = ReadAndWriteContract::new(nftContract);
-
-            Contract::ReadAndWrite(nftContract)
-        } else {
-            let nftContract = nftContract(
-                public_inner.clone(),
-                JsValue::undefined(),
-                NFT_ADDRESS.to_string(),
-            );
+        selected_provider().map(|provider| {
+            ViemWalletClient::new(format!("{:#x}", ARBITRUM_CHAIN_ID), provider.inner())
+        })
+    });
 
-            let nftContract = ReadOnlyContract::new(nftContract);
+    // remember the wallet and chain so that we can reconnect without a popup next time
+    create_effect(move |_| {
+        if !wallet_enabled() {
+            return;
+        }
 
-            Contract::ReadOnly(nftContract)
+        let (Some(provider), Some(chain_id)) = (selected_provider(), chain_id()) else {
+            return;
         };
 
-        let the_window = window().expect("no window");
-
-        Reflect::set(&the_window, &"nftContract".into(), &contract.inner())
-            .expect("failed to set nft contract");
-
-        contract
-    };
-
-    let nft_contract_address = move || {
-        nft_contract()
-            .address()
-            .expect("this will always have an address")
-    };
-
-    let game_contract_address = create_resource(nft_contract, |nft_contract| async move {
-        nft_contract
-            .read("gameLogic", &JsValue::undefined(), &JsValue::undefined())
-            .await
-            .expect("failed to get game logic")
-            .as_string()
-            .expect("game logic is not a string")
+        all_providers.with(|x| {
+            if let Some(detail) = x.iter().find(|x| x.provider() == &provider) {
+                storage::save_wallet(&storage::SavedWallet::new(detail, chain_id));
+            }
+        });
     });
 
-    let game_contract = move || {
-        let public_inner = public_client.with(|x| x.inner());
-
-        match (game_contract_address(), wallet_client()) {
-            (Some(game_contract_address), Some(wallet_client)) => {
-                let nftContract = gameContract(
-                    public_inner.clone(),
-                    wallet_client.inner(),
-                    game_contract_address,
-                );
+    // `eth_accounts` never prompts the user. it is empty if they revoked our access since their last visit
+    let reconnect = create_action(move |input: &(eip1193::EIP1193Provider, u64)| {
+        let (provider, saved_chain_id) = input.clone();
 
-                // TODO: type specific to the game contract
-                let nftContract = ReadAndWriteContract::new(nftContract);
+        async move {
+            match provider.accounts().await {
+                Ok(accounts) if !accounts.is_empty() => {}
+                Ok(_) => {
+                    log!("saved wallet no longer has any accounts for us");
+                    return;
+                }
+                Err(err) => {
+                    logging::warn!("failed to reconnect: {}", err);
+                    return;
+                }
+            }
 
-                Some(Contract::ReadAndWrite(nftContract))
+            // they might have picked a different wallet while we were waiting
+            if selected_provider.get_untracked().as_ref() != Some(&provider) {
+                return;
             }
-            (Some(game_contract_address), None) => {
-                let nftContract = gameContract(
-                    public_inner.clone(),
-                    JsValue::undefined(),
-                    game_contract_address,
-                );
 
-                let nftContract = ReadOnlyContract::new(nftContract);
+            set_wallet_enabled(true);
 
-                Some(Contract::ReadOnly(nftContract))
-            }
-            _ => None,
-        }
-    };
+            // switching prompts, so only do it if the wallet moved to a different chain since last time
+            match provider.chain_id().await {
+                Ok(x) if x == saved_chain_id => {}
+                Ok(_) => {
+                    let switched = match chains::chain_info(saved_chain_id) {
+                        Some(chain) => provider.switch_or_add_chain(chain).await,
+                        None => provider.switch_chain(saved_chain_id).await,
+                    };
 
-    let accounts: Resource<Option<ViemWalletClient>, Vec<String>> =
-        create_resource(wallet_client, |wallet_client| async move {
-            if let Some(wallet_client) = wallet_client {
-                wallet_client
-                    .request_addresses()
-                    .await
-                    .expect("request addresses failed")
-            } else {
-                vec![]
+                    if let Err(err) = switched {
+                        log!("didn't switch back to {:#x}: {}", saved_chain_id, err);
+                        set_provider_error(Some(err));
+                    }
+                }
+                Err(err) => logging::warn!("failed to get the wallet's chain: {}", err),
             }
-        });
-
-    // TODO: eventually emit_missed should be a user option
-    // TODO: if another provider is chosen, this subscription should be ended
-    let block_sub = defaultPublicClient.watch_heads(set_latest_block_header, EMIT_MISSED);
+        }
+    });
 
-    let block_subscription = Rc::new(Mutex::new((block_sub, defaultPublicClient.inner())));
+    let (siwe_session, set_siwe_session) = create_signal::<Option<siwe::SiweSession>>(None);
 
-    let announce_provider_callback = Closure::wrap(Box::new(move |event: web_sys::CustomEvent| {
-        let detail = event.detail();
+    // a session is only good for the account and chain that signed it
+    create_effect(move |_| {
+        let still_valid = match (siwe_session(), wallet_accounts().first(), chain_id()) {
+            (Some(session), Some(account), Some(chain_id)) => {
+                session.is_valid_for(*account, chain_id)
+            }
+            (None, _, _) => true,
+            _ => false,
+        };
 
-        let info = Reflect::get(&detail, &"info".into()).expect("no info");
+        if !still_valid {
+            set_siwe_session(None);
+        }
+    });
 
-        let info: eip6963::EIP6963ProviderInfo =
-            serde_wasm_bindgen::from_value(info).expect("invalid info");
+    let sign_in = create_action(move |provider: &eip1193::EIP1193Provider| {
+        let provider = provider.clone();
 
-        logging::log!("{:?}", info);
+        async move {
+            let session = provider
+                .sign_in_with_ethereum(Some("Sign in to Alderson Dice.".to_string()))
+                .await?;
 
-        let provider_value = Reflect::get(&detail, &"provider".into()).unwrap();
+            set_siwe_session(Some(session));
 
-        let provider = eip1193::EIP1193Provider::new(provider_value).unwrap();
+            Ok::<_, siwe::SiweError>(())
+        }
+    });
 
-        logging::log!("{:?}", provider);
+    let public_client = {
+        let defaultPublicClient = defaultPublicClient.clone();
+        let rpc = rpc.clone();
 
-        set_all_providers.update(|x| {
-            // TODO: this should probably get the whole eip6963 object
-            // TODO: de-dupe?
-            x.push(provider);
-
-            match x.len() {
-                0 => unimplemented!(),
-                1 => set_selected_provider(x.first().cloned()),
-                _ => {
-                    log!(
-                        "multiple providers are not supported yet. continuing to use the first one"
-                    );
-                }
+        create_memo(move |_| {
+            if wallet_client.with(|x| x.is_none()) {
+                return defaultPublicClient.clone();
             }
-        });
-    }) as Box<dyn FnMut(_)>);
 
-    // TODO: this action feels wrong. we fire it from a button press but also from an event listener
-    // TODO: i think most of the things in here should be moved to derived signals
-    let switch_chain = create_action(move |input: &(eip1193::EIP1193Provider, String, String)| {
-        let (provider, chain_id, desired_chain_id) = input.clone();
-        let defaultPublicClient = defaultPublicClient.clone();
-        let block_subscription = block_subscription.clone();
+            let provider = selected_provider().map(|x| x.inner());
 
-        async move {
-            // TODO: there should maybe be a match here? otherwise i think this is just wrong overall and not how components/actions should work
-            if desired_chain_id.is_empty() {
-                if !chain_id.is_empty() {
-                    // TODO: really not sure about this. it feels wrong. but this lets us disconnect the wallet
-                    set_wallet_client(None);
-                    set_public_client(defaultPublicClient.clone());
+            ViemPublicClient::new(format!("{:#x}", ARBITRUM_CHAIN_ID), provider, rpc.clone())
+        })
+    };
 
-                    // this should be inside the Drop on WalletClient
-                    {
-                        let mut lock = block_subscription.lock().unwrap();
+    let (latest_block_head, set_latest_block_header) =
+        create_signal::<Option<eip1193::BlockHeader>>(None);
 
-                        let (sub, context) = (&lock.0, &lock.1);
+    let latest_block_number =
+        move || latest_block_head.with(|x| x.as_ref().map(|x| x.number.as_u64()));
 
-                        // TODO: this should just be a warning
-                        let returned = sub.call0(context).expect("failed to unsubscribe");
+    let latest_block_timestamp =
+        move || latest_block_head.with(|x| x.as_ref().map(|x| x.timestamp.as_u64()));
 
-                        log!("unsubscribed: {:?}", returned);
+    let latest_block_hash = move || latest_block_head.with(|x| x.as_ref().map(|x| x.hash));
 
-                        let sub =
-                            defaultPublicClient.watch_heads(set_latest_block_header, EMIT_MISSED);
+    let nft_address: Address = NFT_ADDRESS.parse().expect("nft address is invalid");
 
-                        *lock = (sub, defaultPublicClient.inner());
-                    }
+    // typed contract reads. these go through the same rpcs as the public client
+    let reader_client = create_memo({
+        let rpc = rpc.clone();
 
-                    set_chain_id("".to_string());
-                }
-            } else if chain_id != desired_chain_id {
-                // <https://github.com/ethereum/EIPs/blob/master/EIPS/eip-1102.md>
-                // <https://github.com/ethereum/EIPs/blob/master/EIPS/eip-2255.md>
-                // <https://eips.ethereum.org/EIPS/eip-3326>
-                #[derive(serde::Serialize)]
-                struct Params<'a> {
-                    chainId: &'a str,
+        move |_| {
+            let transport = match selected_provider() {
+                Some(provider) if wallet_client.with(|x| x.is_some()) => {
+                    ReadTransport::Wallet(provider)
                 }
+                _ => ReadTransport::Rpc(rpc.clone()),
+            };
 
-                // TODO: convenience method for this
-                let params = serde_wasm_bindgen::to_value(&[Params {
-                    chainId: &desired_chain_id,
-                }])
-                .unwrap();
-
-                // we don't actually need to do anything with this result because we have hooks for the chain id changing elsewhere
-                let switched = provider
-                    .request("wallet_switchEthereumChain", Some(&params))
-                    .await
-                    .expect("failed to switch chain");
-
-                log!("switched: {:?}", switched);
+            PublicClient::new(transport)
+        }
+    });
 
-                set_chain_id(desired_chain_id);
-            } else {
-                // TODO: this should maybe be a separate action tied to provider changing?
-                let wallet =
-                    viem::ViemWalletClient::new(desired_chain_id.clone(), provider.inner());
+    let nft_contract = move || NftReader::new(reader_client(), nft_address);
 
-                let public = viem::ViemPublicClient::new(desired_chain_id, Some(provider.inner()));
+    let game_contract_address = create_resource(nft_contract, |nft_contract| async move {
+        match nft_contract.game_logic().await {
+            Ok(x) => Some(x),
+            Err(err) => {
+                logging::warn!("failed to get game logic: {}", err);
+                None
+            }
+        }
+    });
 
-                set_wallet_client(Some(wallet.clone()));
-                set_public_client(public.clone());
+    let game_contract = move || {
+        game_contract_address()
+            .flatten()
+            .map(|x| GameReader::new(reader_client(), x))
+    };
 
-                // TODO: DRY
-                {
-                    let mut lock = block_subscription.lock().unwrap();
+    let accounts = move || {
+        if wallet_client.with(|x| x.is_some()) {
+            wallet_accounts()
+                .into_iter()
+                .map(|x| format!("{:?}", x))
+                .collect()
+        } else {
+            vec![]
+        }
+    };
 
-                    let (sub, context) = (&lock.0, &lock.1);
+    // TODO: eventually emit_missed should be a user option
+    // when the public client changes, stop watching the old one and watch the new one
+    create_effect(move |previous: Option<BlockWatcher>| {
+        // dropping unsubscribes
+        drop(previous);
+
+        // the wallet might push new heads to us. the public rpcs have to be polled
+        let provider = selected_provider().filter(|_| wallet_client.with(|x| x.is_some()));
+
+        BlockWatcher::new(
+            public_client(),
+            provider,
+            &chains::ARBITRUM,
+            EMIT_MISSED,
+            move |x| set_latest_block_header(Some(x)),
+        )
+    });
 
-                    let returned = sub.call0(context).expect("failed to unsubscribe");
+    // TODO: this action feels wrong. we fire it from a button press but also from an event listener
+    let switch_chain = create_action(move |input: &(eip1193::EIP1193Provider, u64)| {
+        let (provider, desired_chain_id) = input.clone();
 
-                    log!("unsubscribed: {:?}", returned);
+        async move {
+            // <https://github.com/ethereum/EIPs/blob/master/EIPS/eip-1102.md>
+            // <https://github.com/ethereum/EIPs/blob/master/EIPS/eip-2255.md>
+            if let Err(err) = provider.request_accounts().await {
+                set_provider_error(Some(err));
+                return;
+            }
 
-                    let sub = public.watch_heads(set_latest_block_header, EMIT_MISSED);
+            // we don't actually need to do anything after this because the wallet client follows the chainChanged event
+            if provider.chain_id_signal().get_untracked() != Some(desired_chain_id) {
+                // if the wallet has never seen the chain, the user is prompted to add it
+                let switched = match chains::chain_info(desired_chain_id) {
+                    Some(chain) => provider.switch_or_add_chain(chain).await,
+                    None => provider.switch_chain(desired_chain_id).await,
+                };
+
+                match switched {
+                    Ok(()) => {
+                        log!("switched to {:#x}", desired_chain_id);
+                    }
+                    Err(err) => {
+                        // a user rejecting is normal. anything else is probably the wallet or the rpc having problems
+                        if err.is_user_rejected() {
+                            log!("user rejected switching chains");
+                        } else {
+                            logging::warn!("failed to switch chain: {}", err);
+                        }
 
-                    *lock = (sub, public.inner());
+                        set_provider_error(Some(err));
+                        return;
+                    }
                 }
-
-                // TODO: save the wallet to localstorage so that we can automatically reconnect to it if we see it again. use the provider uuid or rdns?
-
-                // TODO: we should probably have the bindings in rust instead of js... but then we need to figure out how to handle the provider
-                // TODO: can we use ethers/alloy instead of viem?
             }
 
-            chain_id
+            set_provider_error(None);
+            set_wallet_enabled(true);
+
+            // TODO: we should probably have the bindings in rust instead of js... but then we need to figure out how to handle the provider
+            // TODO: can we use ethers/alloy instead of viem?
         }
     });
 
-    // TODO: shame we don't have automatic types on this
-    let total_dice = create_resource(
-        move || (nft_contract(), latest_block_hash()),
-        |(nft_contract, block_hash)| async move {
-            // TODO: subscribe to logs here. not sure how to have that signal write to this
-
-            // TODO: what option do we add to include this block number/hash in the query
-            let total_dice = nft_contract
-                .read("totalSupply", &JsValue::undefined(), &JsValue::undefined())
-                .await
-                .expect("failed to get total dice");
-
-            let total_dice = total_dice
-                .dyn_into::<BigInt>()
-                .expect("total dice is not a bigint");
-
-            let total_dice = total_dice.to_string(10).unwrap();
+    // TODO: subscribe to logs here instead of reading everything every block
+    let snapshot = create_resource(
+        move || {
+            (
+                reader_client(),
+                game_contract_address().flatten(),
+                latest_block_hash(),
+            )
+        },
+        move |(client, game_address, block_hash)| async move {
+            let (Some(game_address), Some(block_hash)) = (game_address, block_hash) else {
+                return None;
+            };
 
-            format!("{}", total_dice)
+            match GameSnapshot::load(&client, nft_address, game_address, block_hash).await {
+                Ok(x) => Some(x),
+                Err(err) => {
+                    logging::warn!("failed to get the game snapshot: {}", err);
+                    None
+                }
+            }
         },
     );
 
-    let dice_colors = create_resource(game_contract, |game_contract| async move {
-        // TODO: do these concurrently
-        let mut dice = Vec::<DiceColor>::new();
+    // a memo so that things using the colors don't re-render every block
+    let dice_colors = create_memo(move |_| {
+        snapshot.with(|x| x.as_ref().and_then(|x| x.as_ref()).map(|x| x.dice.clone()))
+    });
 
-        // TODO: is there a better way to turn an array into the color?
-        let die_info = game_contract?
-            .read("allDice", &JsValue::undefined(), &JsValue::undefined())
-            .await
-            .expect("failed to get all dice")
-            .dyn_into::<Array>()
-            .expect("color is not an array");
-
-        // TODO: wat? where are the pips?
-        log!("die_info: {:?}", die_info);
-
-        for (i, d) in die_info.into_iter().enumerate() {
-            let d = d.dyn_into::<Object>().expect("d is not an object");
-
-            let name = Reflect::get(&d, &"name".into())
-                .expect("name is not present")
-                .as_string()
-                .expect("name should be a string");
-
-            let symbol = Reflect::get(&d, &"symbol".into())
-                .expect("symbol is not present")
-                .as_string()
-                .expect("symbol should be a string");
-
-            // TODO: converting through f64? eww why
-            let pips = Reflect::get(&d, &"pips".into())
-                .expect("pips is not present")
-                .dyn_into::<Array>()
-                .expect("pips is not an array")
-                .into_iter()
-                .map(|x| x.as_f64().expect("pip is not a number").to_string())
-                .collect::<Vec<_>>();
-
-            let color = DiceColor {
-                id: i,
-                name,
-                symbol,
-                pips,
-            };
+    let saved_wallet = storage::load_wallet();
 
-            dice.push(color);
-        }
+    let add_provider = move |detail: eip6963::EIP6963ProviderDetail| {
+        logging::log!("{:?}", detail);
 
-        log!("dice: {:?}", dice);
+        let saved_chain_id = saved_wallet
+            .as_ref()
+            .filter(|x| x.matches(&detail))
+            .map(|x| x.chain_id);
 
-        Some(dice)
-    });
+        let provider = detail.provider().clone();
 
-    // TODO: split current_bag and current_pips into separate resources?
-    let current_bag = create_resource(
-        move || (game_contract(), latest_block_number(), dice_colors()),
-        |(game_contract, latest_block_number, dice_colors)| async move {
-            match (game_contract, latest_block_number, dice_colors) {
-                (Some(game_contract), Some(latest_block_number), Some(Some(dice_colors))) => {
-                    let current_bag = game_contract
-                        .read("currentBag", &JsValue::undefined(), &JsValue::undefined())
-                        .await
-                        .expect("failed to get current bag")
-                        .dyn_into::<Array>()
-                        .expect("current bag is not an array")
-                        .into_iter()
-                        .map(|x| {
-                            // TODO: there has to be a better way
-                            let color_id = x
-                                .dyn_into::<BigInt>()
-                                .expect("current bag item is not a BigInt")
-                                .to_string(10)
-                                .unwrap()
-                                .as_string()
-                                .expect("current bag bigint is not a string")
-                                .parse::<usize>()
-                                .expect("curent bag string is not a usize")
-                                % dice_colors.len();
-
-                            // TODO: get the dice_colors from the other resource
-
-                            dice_colors[color_id].clone()
-                        })
-                        .collect::<Vec<_>>();
-
-                    // TODO: one rpc call to get the bag and the roll/await these in parallel
-                    let current_pips = game_contract
-                        .read(
-                            "rollCurrentBag",
-                            &JsValue::undefined(),
-                            &JsValue::undefined(),
-                        )
-                        .await
-                        .expect("failed to get current pips")
-                        .dyn_into::<Array>()
-                        .expect("current pips is not an array")
-                        .into_iter()
-                        .map(|x| {
-                            x.dyn_into::<BigInt>()
-                                .expect("pips are a bigint here")
-                                .to_string(10)
-                                .expect("pips should become a JsString")
-                                .as_string()
-                                .expect("pips should become a String")
-                        })
-                        .collect::<Vec<_>>();
+        set_all_providers.update(|x| {
+            if !eip6963::insert_unique(x, detail) {
+                return;
+            }
+
+            if let Some(saved_chain_id) = saved_chain_id {
+                // the wallet they connected with last time
+                set_selected_provider(Some(provider.clone()));
+                reconnect.dispatch((provider, saved_chain_id));
+            } else if x.len() == 1 {
+                // use the first wallet until the user picks a different one
+                set_selected_provider(Some(provider));
+            }
+        });
+    };
+
+    eip6963::request_providers(add_provider.clone());
 
-                    Some((current_bag, current_pips))
+    // wallets that only inject `window.ethereum` never announce themselves
+    set_timeout(
+        move || {
+            if all_providers.with_untracked(|x| x.is_empty()) {
+                for detail in eip6963::legacy_providers() {
+                    add_provider(detail);
                 }
-                _ => None,
             }
         },
+        LEGACY_PROVIDER_TIMEOUT,
     );
 
-    let announce_provider_callback = announce_provider_callback.into_js_value();
-
-    the_window
-        .add_event_listener_with_callback(
-            "eip6963:announceProvider",
-            announce_provider_callback.unchecked_ref(),
-        )
-        .expect("failed to add event listener");
-
-    let request_provider_event = web_sys::CustomEvent::new("eip6963:requestProvider")
-        .expect("failed to create custom event");
-
-    the_window
-        .dispatch_event(&request_provider_event)
-        .expect("failed to dispatch event");
-
     view! {
         <main class="container">
             <h1>"Alderson Dice"</h1>
@@ -561,90 +452,59 @@ fn App() -> impl IntoView {
                 when=move || { selected_provider().is_some() }
                 fallback=|| view! { <UnsupportedBrowser/> }
             >
-                <article>
+                <WalletPicker
+                    all_providers=all_providers
+                    selected_provider=selected_provider
+                    on_select=move |provider| {
+                        set_wallet_enabled(false);
+                        set_provider_error(None);
+                        set_selected_provider(Some(provider));
+                    }
+                />
 
-                    {
-                        let provider: eip1193::EIP1193Provider = selected_provider().unwrap();
-                        let disconnect_chain_args = (
-                            provider.clone(),
-                            chain_id().clone(),
-                            "".to_string(),
-                        );
-                        let switch_chain_args = (
-                            provider.clone(),
-                            chain_id().clone(),
-                            ARBITRUM_CHAIN_ID.to_string(),
-                        );
-                        if chain_id() == ARBITRUM_CHAIN_ID {
-                            switch_chain.dispatch(switch_chain_args);
+                <article>
+                    // TODO: dropdown to change the chain?
+                    // TODO: should also let the user use other wallets like with walletconnect
+                    {move || {
+                        if wallet_client.with(|x| x.is_some()) {
                             view! {
-                                // we call dispatch because we might start with the wallet already being connected. i don't love this
-                                // TODO: don't call chain_id twice? use a resource? is that the right term?
-
-                                // TODO: dropdown to change the chain?
-
-                                // we call dispatch because we might start with the wallet already being connected. i don't love this
-
                                 <button on:click=move |_| {
-                                    switch_chain.dispatch(disconnect_chain_args.clone())
-                                }>
-
-                                    "Disconnect Your Wallet"
-                                </button>
+                                    set_wallet_enabled(false);
+                                    storage::forget_wallet();
+                                }>"Disconnect Your Wallet"</button>
                             }
                                 .into_view()
                         } else {
                             view! {
-                                // we call dispatch because we might start with the wallet already being connected. i don't love this
-                                // TODO: don't call chain_id twice? use a resource? is that the right term?
-
-                                // TODO: dropdown to change the chain?
-
-                                // we call dispatch because we might start with the wallet already being connected. i don't love this
-
-                                // we call dispatch because we might start with the wallet already being connected. i don't love this
-                                // TODO: don't call chain_id twice? use a resource? is that the right term?
-
-                                // TODO: dropdown to change the chain?
-
-                                // we call dispatch because we might start with the wallet already being connected. i don't love this
-
-                                // we call dispatch because we might start with the wallet already being connected. i don't love this
-                                // TODO: don't call chain_id twice? use a resource? is that the right term?
-
-                                // TODO: dropdown to change the chain?
-
-                                // we call dispatch because we might start with the wallet already being connected. i don't love this
-
-                                // we call dispatch because we might start with the wallet already being connected. i don't love this
-                                // TODO: don't call chain_id twice? use a resource? is that the right term?
-
-                                // TODO: dropdown to change the chain?
-
-                                // we call dispatch because we might start with the wallet already being connected. i don't love this
-
-                                // we call dispatch because we might start with the wallet already being connected. i don't love this
-                                // TODO: don't call chain_id twice? use a resource? is that the right term?
-
-                                // TODO: dropdown to change the chain?
-
-                                // we call dispatch because we might start with the wallet already being connected. i don't love this
-
                                 // a button that requests the arbitrum provider and accounts when clicked
-                                // TODO: this should open a modal that lists the user's injected wallets and lets them pick one
-                                // TODO: should also let the user use other wallets like with walletconnect
-                                // TODO: if the user has already clicked the button this session, dipatch now?
                                 <button on:click=move |_| {
-                                    switch_chain.dispatch(switch_chain_args.clone())
+                                    if let Some(provider) = selected_provider() {
+                                        switch_chain.dispatch((provider, ARBITRUM_CHAIN_ID))
+                                    }
                                 }>"Connect Your Wallet to Arbitrum"</button>
                             }
                                 .into_view()
                         }
-                    }
+                    }}
 
                 </article>
             </Show>
 
+            {move || {
+                provider_error()
+                    .map(|err| {
+                        let message = if err.is_user_rejected() {
+                            "You rejected the request in your wallet.".to_string()
+                        } else if err.is_disconnected() {
+                            "Your wallet is disconnected. Please check its network connection."
+                                .to_string()
+                        } else {
+                            format!("Your wallet returned an error: {}", err)
+                        };
+                        view! { <article>{message}</article> }
+                    })
+            }}
+
             // TODO: this should be a resource or maybe an action
             <article>
                 {move || {
@@ -657,37 +517,32 @@ fn App() -> impl IntoView {
 
             </article>
 
+            <RpcDiagnostics rpc=rpc.clone() config=chain_config.clone()/>
+
             <Show when=move || { latest_block_head().is_some() }>
                 <article>
-                    <div>"Block Number: " {latest_block_number}</div>
+                    <div>"Block Number: " {move || latest_block_number().map(|x| x.to_string())}</div>
                     <div>
                         // TODO: component for the block age
-                        "Block Timestamp: " {latest_block_timestamp}
+                        "Block Timestamp: " {move || latest_block_timestamp().map(|x| x.to_string())}
                     </div>
                     <div>
                         // TODO: component for the block hash
-                        "Block Hash: " {latest_block_hash}
+                        "Block Hash: " {move || latest_block_hash().map(|x| format!("{:?}", x))}
                     </div>
                 </article>
 
+                <article>"NFT Contract: " {move || format!("{:?}", nft_address)}</article>
+
                 <article>
-                    "NFT Contract: "
-                    {move || { format!("{} - {:?}", nft_contract_address(), nft_contract()) }}
+                    "Total Dice: "
+                    {move || snapshot().flatten().map(|x| x.total_dice.to_string())}
                 </article>
 
-                <article>"Total Dice: " {total_dice}</article>
-
                 <Show when=move || game_contract().is_some()>
                     <article>
                         "Game Contract: "
-                        {move || {
-                            format!(
-                                "{:?} - {:?}",
-                                game_contract_address()
-                                    .expect("game_contract is set so the address must be too"),
-                                game_contract(),
-                            )
-                        }}
+                        {move || game_contract().map(|x| format!("{:?}", x.address()))}
 
                     </article>
 
@@ -698,7 +553,7 @@ fn App() -> impl IntoView {
                     // TODO: loading spinner
                     // TODO: animation every change
                     <Show
-                        when=move || { current_bag().map(|x| x.is_some()).unwrap_or(false) }
+                        when=move || { snapshot().flatten().is_some() }
                         fallback=|| view! { <article>"Block's dice are loading..."</article> }
                     >
                         <article>
@@ -706,10 +561,11 @@ fn App() -> impl IntoView {
                             // TODO: component for the game's current bag according to the current block
 
                             {
-                                let (current_bag, current_pips) = current_bag().unwrap().unwrap();
-                                current_bag
+                                let snapshot = snapshot().flatten().unwrap();
+                                snapshot
+                                    .current_bag_colors()
                                     .into_iter()
-                                    .zip(current_pips.into_iter())
+                                    .zip(snapshot.current_pips.into_iter())
                                     .map(|(dice_color, pips)| {
                                         view! {
                                             // TODO: is the bag giving us a dice id? if so, then we need to turn that into a color
@@ -717,7 +573,7 @@ fn App() -> impl IntoView {
                                             // TODO: show the number rolled on top of each die
                                             {&dice_color.symbol}
                                             " "
-                                            {pips}
+                                            {pips.to_string()}
                                             " "
                                         }
                                     })
@@ -731,20 +587,64 @@ fn App() -> impl IntoView {
                         // TODO: component to prompt for accounts to watch
                         "Other Account's Dice: " "???"
                     </article>
+
+                    {move || {
+                        game_contract_address()
+                            .flatten()
+                            .map(|game_address| {
+                                view! {
+                                    <ChosenDiceFeed
+                                        client=reader_client()
+                                        game_address=game_address
+                                        nft_address=nft_address
+                                    />
+                                }
+                            })
+                    }}
                 </Show>
             </Show>
 
             // TODO: use `with` here?
-            <Show when=move || match accounts() {
-                None => false,
-                Some(x) => !x.is_empty(),
-            }>
+            <Show when=move || !accounts().is_empty()>
                 // TODO: button to request accounts instead of only doing it on chain switch
                 // this saves them having to hit "disconnect" when they want to add multiple accounts
 
                 <article>
                     // TODO: show accounts as an actual list so they can pick a primary one to manage
-                    "Your Accounts: " {accounts}
+                    "Your Accounts: " {move || format!("{:?}", accounts())}
+                </article>
+
+                <article>
+                    {move || match siwe_session() {
+                        Some(session) => {
+                            format!("Signed in as {:?}", session.address()).into_view()
+                        }
+                        None => {
+                            view! {
+                                <button
+                                    disabled=move || sign_in.pending()()
+                                    on:click=move |_| {
+                                        if let Some(provider) = selected_provider() {
+                                            sign_in.dispatch(provider)
+                                        }
+                                    }
+                                >
+
+                                    "Sign In"
+                                </button>
+                                {move || {
+                                    sign_in
+                                        .value()
+                                        .with(|x| match x {
+                                            Some(Err(err)) => Some(format!(" Sign in failed: {}", err)),
+                                            _ => None,
+                                        })
+                                }}
+                            }
+                                .into_view()
+                        }
+                    }}
+
                 </article>
 
                 // TODO: component for seeing favorite dice
@@ -756,8 +656,42 @@ fn App() -> impl IntoView {
                     "Balances: " "???"
                 </article>
 
-                // TODO: component for buying dice
-                <article>"Buy Dice: " "???"</article>
+                {move || {
+                    match (selected_provider(), game_contract(), dice_colors()) {
+                        (Some(provider), Some(game_contract), Some(dice_colors)) => {
+                            view! {
+                                <AddToWallet
+                                    provider=provider
+                                    nft_contract=nft_contract()
+                                    game_contract=game_contract
+                                    dice_colors=dice_colors
+                                />
+                            }
+                                .into_view()
+                        }
+                        _ => ().into_view(),
+                    }
+                }}
+
+                {move || {
+                    match (
+                        selected_provider(),
+                        wallet_accounts().first().copied(),
+                        game_contract_address().flatten(),
+                    ) {
+                        (Some(provider), Some(account), Some(game_address)) => {
+                            view! {
+                                <BuyDice
+                                    provider=provider
+                                    account=account
+                                    game_address=game_address
+                                />
+                            }
+                                .into_view()
+                        }
+                        _ => view! { <article>"Buy Dice: " "???"</article> }.into_view(),
+                    }
+                }}
 
                 // TODO: component for selling dice
                 <article>"Sell Dice: " "???"</article>
@@ -776,6 +710,437 @@ fn App() -> impl IntoView {
     }
 }
 
+/// AldersonDiceGameV0 was deployed before GameToken and PointsToken, so it has no getters for them. Its prize token
+/// is what players spend like the GameToken, and its vault token is the ERC-4626 share that earns, like the
+/// PointsToken. Use `gameToken()` and `pointsToken()` here once the game is a `GamePiece`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WalletToken {
+    /// the ERC-20 that dice are bought with. the game token for this game
+    PrizeToken,
+    /// the vault that holds the prize token. the points token for this game
+    VaultToken,
+    /// an ERC-6909 id on the NFT contract
+    Die(u64),
+}
+
+/// Buttons that ask the wallet to show our tokens with `wallet_watchAsset`.
+#[component]
+fn AddToWallet(
+    provider: eip1193::EIP1193Provider,
+    nft_contract: NftReader<ReadTransport>,
+    game_contract: GameReader<ReadTransport>,
+    dice_colors: Vec<DieInfo>,
+) -> impl IntoView {
+    let watch_asset = create_action(move |token: &WalletToken| {
+        let provider = provider.clone();
+        let nft_contract = nft_contract.clone();
+        let game_contract = game_contract.clone();
+        let token = *token;
+
+        async move {
+            let asset = match token {
+                WalletToken::PrizeToken => {
+                    let address = game_contract.prize_token().await?;
+
+                    eip747::WatchAsset::erc20(&provider, address).await?
+                }
+                WalletToken::VaultToken => {
+                    let address = game_contract.vault_token().await?;
+
+                    eip747::WatchAsset::erc20(&provider, address).await?
+                }
+                WalletToken::Die(id) => {
+                    eip747::WatchAsset::erc6909(&nft_contract, id.into()).await?
+                }
+            };
+
+            Ok::<_, TransportError>(provider.watch_asset(&asset).await?)
+        }
+    });
+
+    let status = move || {
+        watch_asset.value().with(|x| {
+            x.as_ref().map(|x| match x {
+                Ok(eip747::WatchAssetOutcome::Added) => "Added!".to_string(),
+                Ok(eip747::WatchAssetOutcome::Declined) => "You declined adding it.".to_string(),
+                Ok(eip747::WatchAssetOutcome::Unsupported) => {
+                    "Your wallet doesn't support adding this token.".to_string()
+                }
+                Err(err) => format!("Failed adding the token: {}", err),
+            })
+        })
+    };
+
+    // token ids start at 1. the color is the id modulo the number of colors
+    let num_colors = dice_colors.len() as u64;
+
+    let dice_buttons = (1..=num_colors)
+        .map(|id| {
+            let color = dice_colors[(id % num_colors) as usize].clone();
+
+            view! {
+                <button
+                    title=color.name
+                    on:click=move |_| watch_asset.dispatch(WalletToken::Die(id))
+                >
+                    {color.symbol}
+                </button>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <article>
+            "Add to Wallet: "
+            <button on:click=move |_| {
+                watch_asset.dispatch(WalletToken::PrizeToken)
+            }>"Prize Token"</button>
+            <button on:click=move |_| {
+                watch_asset.dispatch(WalletToken::VaultToken)
+            }>"Vault Token"</button>
+            {dice_buttons}
+            " "
+            {status}
+        </article>
+    }
+}
+
+/// The most recent `ChosenDice` events. Watching stops when this unmounts.
+#[component]
+fn ChosenDiceFeed(
+    client: PublicClient<ReadTransport>,
+    game_address: Address,
+    nft_address: Address,
+) -> impl IntoView {
+    let (chosen, set_chosen) = create_signal(Vec::<events::EventLog>::new());
+
+    let filter = Filter::new()
+        .address(game_address)
+        .topic0(ChosenDiceFilter::signature());
+
+    let (mut events, unwatch) = client.watch_events(
+        game_address,
+        nft_address,
+        filter,
+        block_watcher::polling_interval(&chains::ARBITRUM),
+    );
+
+    spawn_local(async move {
+        while let Some(x) = events.next().await {
+            match x {
+                Ok(x) if x.removed => set_chosen.update(|all| {
+                    all.retain(|y| {
+                        (y.transaction_hash, y.log_index) != (x.transaction_hash, x.log_index)
+                    })
+                }),
+                Ok(x) => set_chosen.update(|all| {
+                    all.insert(0, x);
+                    all.truncate(CHOSEN_DICE_FEED_LEN);
+                }),
+                Err(err) => logging::warn!("failed to watch chosen dice: {}", err),
+            }
+        }
+    });
+
+    on_cleanup(move || drop(unwatch));
+
+    let rows = move || {
+        chosen.with(|all| {
+            all.iter()
+                .filter_map(|x| match &x.event {
+                    events::DiceEvent::ChosenDice(x) => Some(view! {
+                        <li>
+                            {format!("{:?}", x.player)}
+                            " chose "
+                            {x.dice.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")}
+                        </li>
+                    }),
+                    _ => None,
+                })
+                .collect_view()
+        })
+    };
+
+    view! {
+        <article>
+            "Recently Chosen Dice: "
+            <Show when=move || chosen.with(|x| !x.is_empty()) fallback=|| "none yet">
+                <ul>{rows}</ul>
+            </Show>
+        </article>
+    }
+}
+
+/// Every wallet that announced itself. Picking one makes it the active provider.
+#[component]
+fn WalletPicker(
+    all_providers: ReadSignal<Vec<eip6963::EIP6963ProviderDetail>>,
+    selected_provider: ReadSignal<Option<eip1193::EIP1193Provider>>,
+    on_select: impl Fn(eip1193::EIP1193Provider) + Copy + 'static,
+) -> impl IntoView {
+    view! {
+        <article>
+            "Wallets: "
+            <For
+                each=all_providers
+                key=|x| x.uuid().to_string()
+                children=move |detail| {
+                    let provider = detail.provider().clone();
+                    // legacy providers don't have an icon. invalid icons are skipped
+                    let icon = detail.safe_icon();
+                    let is_selected = {
+                        let provider = provider.clone();
+                        create_memo(move |_| {
+                            selected_provider.with(|x| x.as_ref() == Some(&provider))
+                        })
+                    };
+                    view! {
+                        <button
+                            aria-pressed=move || is_selected().to_string()
+                            disabled=is_selected
+                            on:click=move |_| on_select(provider.clone())
+                        >
+                            {icon
+                                .map(|icon| {
+                                    view! {
+                                        <img src=icon alt="" width="24" height="24"/>
+                                        " "
+                                    }
+                                })}
+
+                            {detail.name().to_string()}
+                            {detail.is_legacy().then_some(" (legacy)")}
+                        </button>
+                    }
+                }
+            />
+
+        </article>
+    }
+}
+
+/// Which RPCs the app is reading from and how each of them is doing.
+#[component]
+fn RpcDiagnostics(rpc: Rc<FallbackTransport>, config: chains::ChainConfig) -> impl IntoView {
+    let (status, set_status) = create_signal(rpc.status());
+
+    // health changes with every request. nothing tells us about that, so check every few seconds
+    let handle =
+        set_interval_with_handle(move || set_status(rpc.status()), RPC_DIAGNOSTICS_INTERVAL)
+            .expect("failed to set interval");
+
+    on_cleanup(move || handle.clear());
+
+    let source = config.source;
+    let chain_id = config.chain_id();
+
+    // reload without any `?rpc=`. it would win over whatever is saved
+    let reload_without_query = || {
+        let location = window().location();
+
+        if location.search().unwrap_or_default().is_empty() {
+            location.reload().expect("failed to reload");
+        } else {
+            location.set_search("").expect("failed to clear the query");
+        }
+    };
+
+    let reset = move |_| {
+        storage::forget_rpc_urls(chain_id);
+        reload_without_query();
+    };
+
+    let save = {
+        let config = config.clone();
+
+        move |_| {
+            config.save();
+            reload_without_query();
+        }
+    };
+
+    let rows = move || {
+        status()
+            .into_iter()
+            .map(|x| {
+                let latency = x
+                    .health
+                    .latency_ms
+                    .map(|x| format!("{:.0} ms", x))
+                    .unwrap_or_else(|| "?".to_string());
+
+                view! {
+                    <tr>
+                        <td>{x.url}</td>
+                        <td>{if x.healthy { "ok" } else { "skipped" }}</td>
+                        <td>{latency}</td>
+                        <td>{format!("{} of {}", x.health.errors, x.health.requests)}</td>
+                    </tr>
+                }
+            })
+            .collect_view()
+    };
+
+    view! {
+        <article>
+            <details>
+                <summary>"RPC Diagnostics"</summary>
+                <p>
+                    {config.chain.name} " RPCs "
+                    {match source {
+                        chains::RpcSource::Query => "from the url (only for this visit)",
+                        chains::RpcSource::Storage => "that you saved",
+                        chains::RpcSource::Default => "are the defaults",
+                    }}
+                    ". Add ?rpc=http://127.0.0.1:8545 to the url to use your own."
+                </p>
+                <table>
+                    <thead>
+                        <tr>
+                            <th>"RPC"</th>
+                            <th>"Status"</th>
+                            <th>"Latency"</th>
+                            <th>"Errors"</th>
+                        </tr>
+                    </thead>
+                    <tbody>{rows}</tbody>
+                </table>
+                <Show when=move || source == chains::RpcSource::Query>
+                    <button on:click=save.clone()>"Keep using these RPCs"</button>
+                </Show>
+                <Show when=move || source != chains::RpcSource::Default>
+                    <button on:click=reset>"Use the default RPCs"</button>
+                </Show>
+            </details>
+        </article>
+    }
+}
+
+/// The approval and the buy for `num_dice`. [`BatchCalls::send_calls`] simulates them together before sending.
+async fn buy_dice_calls(
+    provider: &eip1193::EIP1193Provider,
+    account: Address,
+    game_address: Address,
+    num_dice: U256,
+) -> Result<[Call; 2], eip1193::ProviderRpcError> {
+    let price: PriceWithFeesReturn = provider
+        .call_contract(game_address, PriceWithFeesCall)
+        .await?;
+
+    let prize_token: PrizeTokenReturn =
+        provider.call_contract(game_address, PrizeTokenCall).await?;
+
+    // nobody has this many tokens. the simulation fails on the balance instead of this overflowing
+    let cost = price.0.saturating_mul(num_dice);
+
+    Ok([
+        Call::new(
+            prize_token.0,
+            ApproveCall {
+                spender: game_address,
+                amount: cost,
+            },
+        ),
+        Call::new(
+            game_address,
+            BuyNumDiceCall {
+                receiver: account,
+                num_dice,
+            },
+        )
+        .after_previous(),
+    ])
+}
+
+/// Approve the prize token and buy dice. One popup if the wallet can batch calls.
+#[component]
+fn BuyDice(
+    provider: eip1193::EIP1193Provider,
+    account: Address,
+    game_address: Address,
+) -> impl IntoView {
+    let (num_dice, set_num_dice) = create_signal(1u64);
+
+    let (progress, set_progress) = create_signal::<Option<BatchProgress>>(None);
+
+    let buy = create_action(move |num_dice: &u64| {
+        let provider = provider.clone();
+        let num_dice = U256::from(*num_dice);
+
+        async move {
+            set_progress(None);
+
+            let calls = buy_dice_calls(&provider, account, game_address, num_dice).await?;
+
+            provider
+                .send_calls(ARBITRUM_CHAIN_ID, account, &calls, move |x| {
+                    set_progress(Some(x))
+                })
+                .await
+        }
+    });
+
+    let status = move || {
+        match buy.value()() {
+            Some(Err(eip1193::ProviderRpcError::WouldRevert(x))) => {
+                return format!("Not sent because it would fail: {}", x);
+            }
+            Some(Err(err)) => return format!("Buying failed: {}", err),
+            _ => {}
+        }
+
+        match progress() {
+            None if buy.pending()() => "Waiting for your wallet...".to_string(),
+            None => "".to_string(),
+            Some(BatchProgress::Batched { .. }) => {
+                "Sent! Waiting for it to be included...".to_string()
+            }
+            Some(BatchProgress::Sent { index, total, .. }) => {
+                format!(
+                    "Sent {} of {}. Waiting for it to be included...",
+                    index + 1,
+                    total
+                )
+            }
+            Some(BatchProgress::BatchConfirmed { .. }) => "Bought!".to_string(),
+            Some(BatchProgress::Confirmed { index, total, .. }) if index + 1 == total => {
+                "Bought!".to_string()
+            }
+            Some(BatchProgress::Confirmed { index, total, .. }) => {
+                format!(
+                    "{} of {} included. Check your wallet for the next one.",
+                    index + 1,
+                    total
+                )
+            }
+        }
+    };
+
+    view! {
+        <article>
+            "Buy Dice: "
+            <input
+                type="number"
+                min="1"
+                prop:value=num_dice
+                on:input=move |ev| {
+                    if let Ok(x) = event_target_value(&ev).parse() {
+                        set_num_dice(x)
+                    }
+                }
+            />
+            <button
+                disabled=move || buy.pending()()
+                on:click=move |_| buy.dispatch(num_dice.get_untracked())
+            >
+                "Buy"
+            </button>
+            " "
+            {status}
+        </article>
+    }
+}
+
 #[component]
 fn UnsupportedBrowser() -> impl IntoView {
     view! {
@@ -792,11 +1157,60 @@ fn UnsupportedBrowser() -> impl IntoView {
 extern "C" {
     fn hello() -> String;
 
-    fn createPublicClientForChain(chainId: String, eip1193_provider: JsValue) -> JsValue;
+    fn createPublicClientForChain(
+        chainId: String,
+        eip1193_provider: JsValue,
+        rpcProvider: JsValue,
+    ) -> JsValue;
 
     fn createWalletClientForChain(chainId: String, eip1193Provider: JsValue) -> JsValue;
 
-    fn nftContract(publicClient: JsValue, walletClient: JsValue, address: String) -> JsValue;
+}
 
-    fn gameContract(publicClient: JsValue, walletClient: JsValue, address: String) -> JsValue;
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use crate::mock_provider::MockProvider;
+    use ethers::abi::AbiEncode;
+    use ethers::types::Bytes;
+    use serde_json::json;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn encoded(x: impl AbiEncode) -> Bytes {
+        x.encode().into()
+    }
+
+    #[wasm_bindgen_test]
+    async fn buying_dice_approves_the_price() {
+        let account = Address::repeat_byte(0x11);
+        let game_address = Address::repeat_byte(0x22);
+
+        // dice cost 10 each
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+        }));
+
+        mock.respond_once("eth_call", encoded(U256::from(10)))
+            .respond_once("eth_call", encoded(Address::repeat_byte(0x77)));
+
+        let provider = eip1193::EIP1193Provider::new(mock.inner()).unwrap();
+
+        let [approve, buy] = buy_dice_calls(&provider, account, game_address, 2.into())
+            .await
+            .unwrap();
+
+        assert_eq!(approve.to, Address::repeat_byte(0x77));
+        assert_eq!(
+            approve.data,
+            encoded(ApproveCall {
+                spender: game_address,
+                amount: 20.into(),
+            })
+        );
+        assert_eq!(buy.to, game_address);
+        assert!(buy.after_previous);
+    }
 }
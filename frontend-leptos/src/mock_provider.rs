@@ -0,0 +1,314 @@
+//! A scriptable EIP-1193 provider for tests.
+//!
+//! This is a plain javascript object with `request`, `on`, and `removeListener`, so it can be given to anything that
+//! expects a wallet's provider. That includes [`EIP1193Provider::new`](crate::eip1193::EIP1193Provider::new) and
+//! viem's `custom` transport inside `createPublicClientForChain`.
+//!
+//! Requests are answered from scripted JSON-RPC fixtures. Anything that isn't scripted is rejected with 4200
+//! (unsupported method) so that missing fixtures are loud.
+use ethers::types::{Address, Bytes};
+use js_sys::{Function, Object, Promise, Reflect};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+
+/// What the mock does when it receives a request.
+#[derive(Clone, Debug)]
+pub enum MockResponse {
+    Result(Value),
+    Error {
+        code: i64,
+        message: String,
+        data: Option<Value>,
+    },
+}
+
+#[derive(Default)]
+struct MockState {
+    /// used once each, in order. these are checked before `sticky`
+    once: HashMap<String, VecDeque<MockResponse>>,
+    /// used every time
+    sticky: HashMap<String, MockResponse>,
+    listeners: HashMap<String, Vec<JsValue>>,
+    requests: Vec<(String, Value)>,
+}
+
+impl MockState {
+    fn respond(&mut self, method: &str) -> MockResponse {
+        if let Some(x) = self.once.get_mut(method).and_then(|x| x.pop_front()) {
+            return x;
+        }
+
+        self.sticky
+            .get(method)
+            .cloned()
+            .unwrap_or_else(|| MockResponse::Error {
+                code: 4200,
+                message: format!("no fixture for {}", method),
+                data: None,
+            })
+    }
+}
+
+#[derive(Clone)]
+pub struct MockProvider {
+    inner: Object,
+    state: Rc<RefCell<MockState>>,
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        let inner = Object::new();
+        let state: Rc<RefCell<MockState>> = Default::default();
+
+        let request = {
+            let state = state.clone();
+
+            Closure::wrap(Box::new(move |args: JsValue| -> Promise {
+                let method = Reflect::get(&args, &"method".into())
+                    .ok()
+                    .and_then(|x| x.as_string())
+                    .unwrap_or_default();
+
+                let params = Reflect::get(&args, &"params".into()).unwrap_or_default();
+
+                let params: Value = serde_wasm_bindgen::from_value(params).unwrap_or(Value::Null);
+
+                let response = {
+                    let mut state = state.borrow_mut();
+
+                    state.requests.push((method.clone(), params));
+
+                    state.respond(&method)
+                };
+
+                match response {
+                    MockResponse::Result(x) => Promise::resolve(&to_js(&x)),
+                    MockResponse::Error {
+                        code,
+                        message,
+                        data,
+                    } => {
+                        let mut err = json!({ "code": code, "message": message });
+
+                        if let Some(data) = data {
+                            err["data"] = data;
+                        }
+
+                        Promise::reject(&to_js(&err))
+                    }
+                }
+            }) as Box<dyn FnMut(JsValue) -> Promise>)
+        };
+
+        let on = {
+            let state = state.clone();
+
+            Closure::wrap(Box::new(move |event: String, listener: JsValue| {
+                state
+                    .borrow_mut()
+                    .listeners
+                    .entry(event)
+                    .or_default()
+                    .push(listener);
+            }) as Box<dyn FnMut(String, JsValue)>)
+        };
+
+        let remove_listener = {
+            let state = state.clone();
+
+            Closure::wrap(Box::new(move |event: String, listener: JsValue| {
+                if let Some(x) = state.borrow_mut().listeners.get_mut(&event) {
+                    x.retain(|x| x != &listener);
+                }
+            }) as Box<dyn FnMut(String, JsValue)>)
+        };
+
+        Reflect::set(&inner, &"request".into(), &request.into_js_value()).unwrap();
+        Reflect::set(&inner, &"on".into(), &on.into_js_value()).unwrap();
+        Reflect::set(
+            &inner,
+            &"removeListener".into(),
+            &remove_listener.into_js_value(),
+        )
+        .unwrap();
+
+        Self { inner, state }
+    }
+
+    /// Build a mock from a JSON object of `method => result`. Every result is sticky.
+    pub fn from_fixtures(fixtures: Value) -> Self {
+        let x = Self::new();
+
+        for (method, result) in fixtures.as_object().expect("fixtures are an object") {
+            x.respond(method, result.clone());
+        }
+
+        x
+    }
+
+    pub fn inner(&self) -> JsValue {
+        self.inner.clone().into()
+    }
+
+    /// Answer every `method` request with `result`.
+    pub fn respond(&self, method: &str, result: impl Serialize) -> &Self {
+        let result = serde_json::to_value(result).expect("result is json");
+
+        self.state
+            .borrow_mut()
+            .sticky
+            .insert(method.to_string(), MockResponse::Result(result));
+
+        self
+    }
+
+    /// Answer the next `method` request with `result`. Queued responses are used before sticky ones.
+    pub fn respond_once(&self, method: &str, result: impl Serialize) -> &Self {
+        let result = serde_json::to_value(result).expect("result is json");
+
+        self.push_once(method, MockResponse::Result(result))
+    }
+
+    /// Reject the next `method` request like a wallet would. For example, 4001 if the user clicked reject.
+    pub fn reject_once(&self, method: &str, code: i64, message: &str) -> &Self {
+        self.push_once(
+            method,
+            MockResponse::Error {
+                code,
+                message: message.to_string(),
+                data: None,
+            },
+        )
+    }
+
+    /// Reject the next `method` request like a node does when the call reverts.
+    pub fn revert_once(&self, method: &str, data: impl Into<Bytes>) -> &Self {
+        self.push_once(
+            method,
+            MockResponse::Error {
+                code: 3,
+                message: "execution reverted".to_string(),
+                data: Some(json!(data.into())),
+            },
+        )
+    }
+
+    fn push_once(&self, method: &str, response: MockResponse) -> &Self {
+        self.state
+            .borrow_mut()
+            .once
+            .entry(method.to_string())
+            .or_default()
+            .push_back(response);
+
+        self
+    }
+
+    /// Every request that has been made so far as `(method, params)`.
+    pub fn requests(&self) -> Vec<(String, Value)> {
+        self.state.borrow().requests.clone()
+    }
+
+    /// The params of every `method` request so far.
+    pub fn requests_for(&self, method: &str) -> Vec<Value> {
+        self.state
+            .borrow()
+            .requests
+            .iter()
+            .filter(|(m, _)| m == method)
+            .map(|(_, p)| p.clone())
+            .collect()
+    }
+
+    pub fn listener_count(&self, event: &str) -> usize {
+        self.state
+            .borrow()
+            .listeners
+            .get(event)
+            .map(|x| x.len())
+            .unwrap_or_default()
+    }
+
+    /// Call every listener for `event` like the wallet would.
+    pub fn emit(&self, event: &str, value: &JsValue) {
+        // clone them out in case a listener adds or removes listeners
+        let listeners = self
+            .state
+            .borrow()
+            .listeners
+            .get(event)
+            .cloned()
+            .unwrap_or_default();
+
+        for listener in listeners {
+            listener
+                .unchecked_ref::<Function>()
+                .call1(&JsValue::undefined(), value)
+                .expect("listener threw");
+        }
+    }
+
+    /// The user switched networks in their wallet. This also updates the `eth_chainId` fixture.
+    pub fn emit_chain_changed(&self, chain_id: u64) {
+        let chain_id = format!("{:#x}", chain_id);
+
+        self.respond("eth_chainId", &chain_id);
+
+        self.emit("chainChanged", &chain_id.into());
+    }
+
+    /// The user switched or disconnected accounts in their wallet. This also updates the `eth_accounts` fixture.
+    pub fn emit_accounts_changed(&self, accounts: &[Address]) {
+        self.respond("eth_accounts", accounts);
+
+        self.emit("accountsChanged", &to_js(&json!(accounts)));
+    }
+
+    pub fn emit_disconnect(&self) {
+        self.emit(
+            "disconnect",
+            &to_js(&json!({ "code": 4900, "message": "disconnected" })),
+        );
+    }
+
+    /// Deliver an `eth_subscription` message.
+    pub fn emit_subscription(&self, subscription: &str, result: Value) {
+        let message = json!({
+            "type": "eth_subscription",
+            "data": {
+                "subscription": subscription,
+                "result": result,
+            },
+        });
+
+        self.emit("message", &to_js(&message));
+    }
+}
+
+fn to_js(x: &Value) -> JsValue {
+    x.serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .expect("json is always a valid js value")
+}
+
+/// Let pending promises and `spawn_local` tasks run.
+pub async fn next_tick() {
+    sleep(0).await
+}
+
+pub async fn sleep(ms: i32) {
+    let promise = Promise::new(&mut |resolve, _| {
+        let set_timeout = Reflect::get(&js_sys::global(), &"setTimeout".into())
+            .unwrap()
+            .unchecked_into::<Function>();
+
+        set_timeout
+            .call2(&JsValue::undefined(), &resolve, &ms.into())
+            .unwrap();
+    });
+
+    wasm_bindgen_futures::JsFuture::from(promise).await.unwrap();
+}
@@ -0,0 +1,84 @@
+//! Many `eth_call`s in one request through [Multicall3](https://github.com/mds1/multicall).
+//!
+//! Every call in a batch runs against the same block, so the results are consistent with each other.
+use crate::public_client::PublicClient;
+use crate::transport::{Transport, TransportError};
+use ethers::abi::AbiDecode;
+use ethers::contract::{abigen, EthCall};
+use ethers::types::{Address, BlockId, Bytes};
+
+/// Multicall3 is deployed at the same address on every chain that we support.
+pub const MULTICALL3_ADDRESS: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+// the real abi calls the return struct `Result`. that name would shadow std's
+abigen!(
+    Multicall3,
+    r#"[
+        struct Call3 { address target; bool allowFailure; bytes callData; }
+        struct Result3 { bool success; bytes returnData; }
+        function aggregate3(Call3[] calls) external payable returns (Result3[] returnData)
+    ]"#
+);
+
+/// Calls to send together. If any of them revert, the whole batch fails.
+#[derive(Clone, Debug, Default)]
+pub struct Multicall {
+    calls: Vec<Call3>,
+}
+
+impl Multicall {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a call. The results come back in the order the calls were added.
+    pub fn add(&mut self, target: Address, call: impl EthCall) -> &mut Self {
+        self.calls.push(Call3 {
+            target,
+            allow_failure: false,
+            call_data: call.encode().into(),
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// One `eth_call` to Multicall3 at `block`. Returns each call's ABI encoded return data.
+    pub async fn call<T: Transport>(
+        self,
+        client: &PublicClient<T>,
+        block: BlockId,
+    ) -> Result<Vec<Bytes>, TransportError> {
+        let num_calls = self.calls.len();
+
+        let multicall = MULTICALL3_ADDRESS
+            .parse()
+            .expect("multicall address is invalid");
+
+        let Aggregate3Return { return_data } = client
+            .call_contract(multicall, Aggregate3Call { calls: self.calls }, block)
+            .await?;
+
+        if return_data.len() != num_calls {
+            return Err(TransportError::InvalidResponse(format!(
+                "multicall returned {} results for {} calls",
+                return_data.len(),
+                num_calls
+            )));
+        }
+
+        // allow_failure is false, so every call succeeded
+        Ok(return_data.into_iter().map(|(_success, x)| x).collect())
+    }
+}
+
+/// Decode one result from [`Multicall::call`]. Single return values are 1-tuples like `(U256,)`.
+pub fn decode<R: AbiDecode>(data: Bytes) -> Result<R, TransportError> {
+    R::decode(data).map_err(|err| TransportError::InvalidResponse(err.to_string()))
+}
@@ -0,0 +1,313 @@
+//! Typed chain reads on top of any [`Transport`].
+use crate::backfill::{Backfill, BackfillStep, Checkpoint};
+use crate::eip1193::BlockHeader;
+use crate::events::EventLog;
+use crate::transport::{Transport, TransportError};
+use ethers::abi::{AbiDecode, AbiEncode};
+use ethers::types::{
+    Address, Block, BlockId, BlockNumber, Bytes, Filter, Log, TransactionRequest, H256, U64,
+};
+use futures::channel::mpsc;
+use futures::future::{AbortHandle, Abortable};
+use futures::{Stream, StreamExt};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use wasm_bindgen_futures::spawn_local;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublicClient<T> {
+    transport: T,
+}
+
+impl<T: Transport> PublicClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// `eth_chainId`
+    pub async fn chain_id(&self) -> Result<u64, TransportError> {
+        let x: U64 = self.transport.request("eth_chainId", &()).await?;
+
+        Ok(x.as_u64())
+    }
+
+    /// `eth_blockNumber`
+    pub async fn block_number(&self) -> Result<u64, TransportError> {
+        let x: U64 = self.transport.request("eth_blockNumber", &()).await?;
+
+        Ok(x.as_u64())
+    }
+
+    /// `eth_getBlockByNumber` without the full transactions. None if the block doesn't exist yet.
+    pub async fn get_block_by_number(
+        &self,
+        number: BlockNumber,
+    ) -> Result<Option<Block<H256>>, TransportError> {
+        self.transport
+            .request("eth_getBlockByNumber", &(number, false))
+            .await
+    }
+
+    /// [`Self::get_block_by_number`] with only the fields in a [`BlockHeader`].
+    pub async fn get_block_header(
+        &self,
+        number: BlockNumber,
+    ) -> Result<Option<BlockHeader>, TransportError> {
+        self.transport
+            .request("eth_getBlockByNumber", &(number, false))
+            .await
+    }
+
+    /// `eth_call`
+    pub async fn call(
+        &self,
+        tx: &TransactionRequest,
+        block: BlockId,
+    ) -> Result<Bytes, TransportError> {
+        self.transport.request("eth_call", &(tx, block)).await
+    }
+
+    /// `eth_call` a contract with ABI encoded calldata and decode what it returns.
+    pub async fn call_contract<C: AbiEncode, R: AbiDecode>(
+        &self,
+        to: Address,
+        call: C,
+        block: BlockId,
+    ) -> Result<R, TransportError> {
+        let tx = TransactionRequest::new().to(to).data(call.encode());
+
+        let x = self.call(&tx, block).await?;
+
+        R::decode(x).map_err(|err| TransportError::InvalidResponse(err.to_string()))
+    }
+
+    /// `eth_getLogs`
+    pub async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>, TransportError> {
+        self.transport.request("eth_getLogs", &[filter]).await
+    }
+}
+
+impl<T: Transport + 'static> PublicClient<T> {
+    /// Events that match `filter` from the next block on, checking for new blocks every `interval`.
+    ///
+    /// Logs that a reorg drops are sent again with `removed` set, the same as `eth_subscribe` does. The stream ends
+    /// once the [`Unwatch`] is dropped.
+    pub fn watch_events(
+        self,
+        game: Address,
+        nft: Address,
+        filter: Filter,
+        interval: Duration,
+    ) -> (EventStream, Unwatch) {
+        let (sender, receiver) = mpsc::unbounded();
+
+        let (abort, registration) = AbortHandle::new_pair();
+
+        let task = async move {
+            let head = match self.block_number().await {
+                Ok(x) => x,
+                Err(err) => {
+                    let _ = sender.unbounded_send(Err(err));
+                    return;
+                }
+            };
+
+            let mut steps = Box::pin(
+                Backfill::new(self, game, nft, filter, Checkpoint::new(head + 1)).live(interval),
+            );
+
+            // what a reorg could still take back
+            let mut delivered: Vec<EventLog> = vec![];
+
+            while let Some(x) = steps.next().await {
+                let x = match x {
+                    Ok((BackfillStep::Logs { logs, .. }, checkpoint)) => {
+                        // reorgs deeper than the checkpoint remembers start over. those logs aren't taken back
+                        if let Some(&(oldest, _)) = checkpoint.hashes.first() {
+                            delivered.retain(|x| x.block_number > Some(oldest));
+                        }
+
+                        delivered.extend(logs.iter().cloned());
+
+                        logs.into_iter().map(Ok).collect()
+                    }
+                    Ok((BackfillStep::Reorg { from_block }, _)) => {
+                        let (removed, kept): (Vec<_>, Vec<_>) = delivered
+                            .drain(..)
+                            .partition(|x| x.block_number >= Some(from_block));
+
+                        delivered = kept;
+
+                        removed
+                            .into_iter()
+                            .map(|x| Ok(EventLog { removed: true, ..x }))
+                            .collect()
+                    }
+                    Ok((BackfillStep::CaughtUp, _)) => vec![],
+                    Err(err) => vec![Err(err)],
+                };
+
+                for x in x {
+                    if sender.unbounded_send(x).is_err() {
+                        // the receiver is gone. nobody is listening
+                        return;
+                    }
+                }
+            }
+        };
+
+        spawn_local(async move {
+            // aborting drops the sender, which ends the stream
+            let _ = Abortable::new(task, registration).await;
+        });
+
+        (EventStream { receiver }, Unwatch { abort })
+    }
+}
+
+/// Decoded events from [`PublicClient::watch_events`].
+pub struct EventStream {
+    receiver: mpsc::UnboundedReceiver<Result<EventLog, TransportError>>,
+}
+
+impl Stream for EventStream {
+    type Item = Result<EventLog, TransportError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+/// Stops polling for events when dropped. This also ends the [`EventStream`].
+#[must_use = "the events stop being watched when this is dropped"]
+pub struct Unwatch {
+    abort: AbortHandle,
+}
+
+impl Drop for Unwatch {
+    fn drop(&mut self) {
+        self.abort.abort();
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use crate::bindings::game::SetMintDevFeeFilter;
+    use crate::eip1193::EIP1193Provider;
+    use crate::events::DiceEvent;
+    use crate::mock_provider::MockProvider;
+    use ethers::contract::EthEvent;
+    use ethers::types::U256;
+    use serde_json::json;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    async fn watches_events_until_unwatched() {
+        let game = Address::repeat_byte(0x22);
+        let hash = H256::repeat_byte(0xab);
+
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+            "eth_getBlockByNumber": {
+                "number": "0x11",
+                "hash": hash,
+                "parentHash": H256::repeat_byte(0xaa),
+                "timestamp": "0x665b4c00",
+                "miner": Address::zero(),
+                "gasLimit": "0x4000000000000",
+                "gasUsed": "0x0",
+            },
+            "eth_getLogs": [{
+                "address": game,
+                "topics": [SetMintDevFeeFilter::signature()],
+                "data": U256::from(100).encode_hex(),
+                "blockNumber": "0x11",
+                "blockHash": hash,
+                "transactionHash": H256::repeat_byte(0xcd),
+                "transactionIndex": "0x0",
+                "logIndex": "0x0",
+                "removed": false,
+            }],
+        }));
+
+        mock.respond_once("eth_blockNumber", "0x10")
+            .respond("eth_blockNumber", "0x11");
+
+        let client = PublicClient::new(EIP1193Provider::new(mock.inner()).unwrap());
+
+        let (mut events, unwatch) = client.watch_events(
+            game,
+            Address::repeat_byte(0x33),
+            Filter::new().address(game),
+            Duration::from_millis(50),
+        );
+
+        let x = events.next().await.unwrap().unwrap();
+
+        assert_eq!(
+            x.event,
+            DiceEvent::SetMintDevFee(SetMintDevFeeFilter {
+                new_fee: 100.into()
+            })
+        );
+        assert_eq!(x.block_number, Some(17));
+
+        // starts after the head at the time of watching
+        assert_eq!(
+            mock.requests_for("eth_getLogs")[0][0]["fromBlock"],
+            json!("0x11")
+        );
+
+        drop(unwatch);
+
+        assert!(events.next().await.is_none());
+    }
+
+    #[wasm_bindgen_test]
+    async fn reads_through_a_wallet() {
+        let hash = H256::repeat_byte(0xab);
+
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+            "eth_blockNumber": "0x10",
+            "eth_getBlockByNumber": {
+                "number": "0x10",
+                "hash": hash,
+                "parentHash": H256::repeat_byte(0xaa),
+                "timestamp": "0x665b4c00",
+                "gasLimit": "0x4000000000000",
+                "gasUsed": "0x0",
+                "baseFeePerGas": "0x989680",
+                "transactions": [],
+            },
+        }));
+
+        let client = PublicClient::new(EIP1193Provider::new(mock.inner()).unwrap());
+
+        assert_eq!(client.chain_id().await.unwrap(), 0xa4b1);
+        assert_eq!(client.block_number().await.unwrap(), 16);
+
+        let block = client
+            .get_block_by_number(BlockNumber::Number(16.into()))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(block.hash, Some(hash));
+
+        assert_eq!(
+            mock.requests_for("eth_getBlockByNumber"),
+            vec![json!(["0x10", false])]
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Why a call would revert, in words that we can show to the user.
+//!
+//! Writes are simulated with `eth_call` or `eth_simulateV1` first. If the simulation reverts, the transaction is never sent and the
+//! user sees one of these instead of a wallet popup for a transaction that can only fail.
+use crate::bindings::game::AldersonDiceGameV0Errors;
+use crate::bindings::nft::AldersonDiceNFTErrors;
+use crate::eip1193::ProviderRpcError;
+use ethers::abi::AbiDecode;
+use ethers::types::{Bytes, U256};
+use js_sys::Reflect;
+use wasm_bindgen::JsValue;
+
+/// `Error(string)`. What `require(..., "message")` reverts with.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// `Panic(uint256)`. Failed asserts, overflows, and the like.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// wallets wrap the node's error in their own. don't dig forever
+const MAX_DEPTH: usize = 5;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Revert {
+    /// `Unauthorized()` or `require(..., "!auth")`
+    Unauthorized,
+    /// `InsufficientBalance()` or `require(..., "!bal")`
+    InsufficientBalance,
+    InsufficientPermission,
+    BalanceOverflow,
+    NewOwnerIsZeroAddress,
+    AlreadyInitialized,
+    NoHandoverRequest,
+    /// `require(..., "!len")`
+    LengthMismatch,
+    /// any other `require` message
+    Message(String),
+    Panic(U256),
+    /// data that doesn't match any of our errors. empty for a bare `revert()`
+    Unknown(Bytes),
+}
+
+impl Revert {
+    /// Decode the data that a reverted call returned.
+    pub fn decode(data: &[u8]) -> Self {
+        // the generated `RevertString` expects the string without its selector
+        if let Some(x) = data.strip_prefix(&ERROR_SELECTOR) {
+            if let Ok(x) = String::decode(x) {
+                return Self::from_message(&x);
+            }
+        }
+
+        if let Some(x) = data.strip_prefix(&PANIC_SELECTOR) {
+            if let Ok(x) = U256::decode(x) {
+                return Self::Panic(x);
+            }
+        }
+
+        if let Ok(x) = AldersonDiceGameV0Errors::decode(data) {
+            return match x {
+                AldersonDiceGameV0Errors::AlreadyInitialized(_) => Self::AlreadyInitialized,
+                AldersonDiceGameV0Errors::NewOwnerIsZeroAddress(_) => Self::NewOwnerIsZeroAddress,
+                AldersonDiceGameV0Errors::NoHandoverRequest(_) => Self::NoHandoverRequest,
+                AldersonDiceGameV0Errors::Unauthorized(_) => Self::Unauthorized,
+                AldersonDiceGameV0Errors::RevertString(x) => Self::from_message(&x),
+            };
+        }
+
+        if let Ok(x) = AldersonDiceNFTErrors::decode(data) {
+            return match x {
+                AldersonDiceNFTErrors::BalanceOverflow(_) => Self::BalanceOverflow,
+                AldersonDiceNFTErrors::InsufficientBalance(_) => Self::InsufficientBalance,
+                AldersonDiceNFTErrors::InsufficientPermission(_) => Self::InsufficientPermission,
+                AldersonDiceNFTErrors::RevertString(x) => Self::from_message(&x),
+            };
+        }
+
+        Self::Unknown(data.to_vec().into())
+    }
+
+    /// The contracts use short `require` messages to save gas.
+    pub fn from_message(x: &str) -> Self {
+        match x {
+            "!auth" => Self::Unauthorized,
+            "!bal" => Self::InsufficientBalance,
+            "!len" => Self::LengthMismatch,
+            x => Self::Message(x.to_string()),
+        }
+    }
+
+    /// None if `err` isn't a revert.
+    pub fn from_rpc_error(err: &ProviderRpcError) -> Option<Self> {
+        let ProviderRpcError::Rpc {
+            code,
+            message,
+            data,
+        } = err
+        else {
+            return None;
+        };
+
+        if let Some(x) = data.as_ref().and_then(|x| find_revert_data(x, 0)) {
+            return Some(Self::decode(&x));
+        }
+
+        // geth uses 3 for reverts. some nodes only say it in the message
+        if code.code() != 3 && !message.to_lowercase().contains("revert") {
+            return None;
+        }
+
+        // some nodes put the reason in the message instead of the data
+        let x = match message.split_once("reverted: ") {
+            Some((_, reason)) => Self::from_message(reason),
+            None => Self::Unknown(Bytes::new()),
+        };
+
+        Some(x)
+    }
+}
+
+fn hex_bytes(x: &JsValue) -> Option<Bytes> {
+    x.as_string()?.parse().ok()
+}
+
+/// Revert data is a hex string. Wallets put it at `data`, `data.data`, or `data.originalError.data`.
+fn find_revert_data(data: &JsValue, depth: usize) -> Option<Bytes> {
+    if let Some(x) = hex_bytes(data) {
+        return Some(x);
+    }
+
+    if depth >= MAX_DEPTH || !data.is_object() {
+        return None;
+    }
+
+    ["data", "originalError"].iter().find_map(|key| {
+        let x = Reflect::get(data, &(*key).into()).ok()?;
+
+        find_revert_data(&x, depth + 1)
+    })
+}
+
+impl std::fmt::Display for Revert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unauthorized => write!(f, "you aren't allowed to do that"),
+            Self::InsufficientBalance => write!(f, "not enough balance"),
+            Self::InsufficientPermission => {
+                write!(f, "this account isn't approved to move those tokens")
+            }
+            Self::BalanceOverflow => write!(f, "that would overflow the balance"),
+            Self::NewOwnerIsZeroAddress => write!(f, "the new owner can't be the zero address"),
+            Self::AlreadyInitialized => write!(f, "already initialized"),
+            Self::NoHandoverRequest => write!(f, "there is no ownership handover to complete"),
+            Self::LengthMismatch => write!(f, "the lists are different lengths"),
+            Self::Message(x) => write!(f, "{}", x),
+            Self::Panic(x) => write!(f, "the contract panicked with code {:#x}", x),
+            Self::Unknown(x) if x.is_empty() => write!(f, "reverted without a reason"),
+            Self::Unknown(x) => write!(f, "reverted with {}", x),
+        }
+    }
+}
+
+impl std::error::Error for Revert {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::game::Unauthorized;
+    use crate::bindings::nft::InsufficientPermission;
+    use ethers::abi::{encode, Token};
+
+    fn revert_string(x: &str) -> Vec<u8> {
+        let mut data = ERROR_SELECTOR.to_vec();
+        data.extend(encode(&[Token::String(x.to_string())]));
+        data
+    }
+
+    #[test]
+    fn decodes_custom_errors() {
+        use ethers::abi::AbiEncode;
+
+        assert_eq!(Revert::decode(&Unauthorized.encode()), Revert::Unauthorized);
+        assert_eq!(
+            Revert::decode(&InsufficientPermission.encode()),
+            Revert::InsufficientPermission
+        );
+    }
+
+    #[test]
+    fn decodes_require_strings() {
+        assert_eq!(
+            Revert::decode(&revert_string("!auth")),
+            Revert::Unauthorized
+        );
+        assert_eq!(
+            Revert::decode(&revert_string("!bal")),
+            Revert::InsufficientBalance
+        );
+        assert_eq!(
+            Revert::decode(&revert_string("!len")),
+            Revert::LengthMismatch
+        );
+        assert_eq!(
+            Revert::decode(&revert_string("too late")),
+            Revert::Message("too late".to_string())
+        );
+    }
+
+    #[test]
+    fn decodes_panics_and_unknown_data() {
+        let mut panic = PANIC_SELECTOR.to_vec();
+        panic.extend(encode(&[Token::Uint(0x11.into())]));
+
+        assert_eq!(Revert::decode(&panic), Revert::Panic(0x11.into()));
+        assert_eq!(Revert::decode(&[]), Revert::Unknown(Bytes::new()));
+        assert_eq!(
+            Revert::decode(&[1, 2, 3, 4]).to_string(),
+            "reverted with 0x01020304"
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Sign-In With Ethereum. <https://eips.ethereum.org/EIPS/eip-4361>
+//!
+//! This proves which account a visitor controls without them sending a transaction.
+//! TODO: there isn't a server yet, so the nonce is made here and the session only lives in the browser.
+use crate::eip1193::{EIP1193Provider, ProviderRpcError};
+use derive_more::From;
+use ethers::types::{Address, Signature, SignatureError};
+use ethers::utils::{hex, to_checksum};
+use js_sys::Date;
+use wasm_bindgen::JsValue;
+
+/// how long a session lasts
+const SESSION_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SiweMessage {
+    /// the host that is asking for the signature. wallets warn if this doesn't match the page
+    pub domain: String,
+    pub address: Address,
+    pub statement: Option<String>,
+    pub uri: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    /// RFC 3339
+    pub issued_at: String,
+    /// RFC 3339
+    pub expiration_time: Option<String>,
+}
+
+impl std::fmt::Display for SiweMessage {
+    /// The exact text that the user signs.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} wants you to sign in with your Ethereum account:",
+            self.domain
+        )?;
+        writeln!(f, "{}", to_checksum(&self.address, None))?;
+        writeln!(f)?;
+
+        if let Some(statement) = &self.statement {
+            writeln!(f, "{}", statement)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "URI: {}", self.uri)?;
+        writeln!(f, "Version: 1")?;
+        writeln!(f, "Chain ID: {}", self.chain_id)?;
+        writeln!(f, "Nonce: {}", self.nonce)?;
+        write!(f, "Issued At: {}", self.issued_at)?;
+
+        if let Some(expiration_time) = &self.expiration_time {
+            write!(f, "\nExpiration Time: {}", expiration_time)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SiweMessage {
+    /// Check that `signature` is from `self.address` signing this message.
+    pub fn verify(&self, signature: &Signature) -> Result<(), SignatureError> {
+        signature.verify(self.to_string(), self.address)
+    }
+}
+
+#[derive(Debug, From)]
+pub enum SiweError {
+    Provider(ProviderRpcError),
+    Signature(SignatureError),
+    /// the wallet didn't give us an account to sign in with
+    NoAccount,
+}
+
+impl std::fmt::Display for SiweError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Provider(x) => write!(f, "{}", x),
+            Self::Signature(x) => write!(f, "bad signature: {}", x),
+            Self::NoAccount => write!(f, "no account to sign in with"),
+        }
+    }
+}
+
+impl std::error::Error for SiweError {}
+
+/// A signed and verified message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SiweSession {
+    pub message: SiweMessage,
+    pub signature: Signature,
+    /// milliseconds since the epoch
+    expires_at: u64,
+}
+
+impl SiweSession {
+    pub fn address(&self) -> Address {
+        self.message.address
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.message.chain_id
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Date::now() as u64 >= self.expires_at
+    }
+
+    /// true if the session is still good for this account on this chain
+    pub fn is_valid_for(&self, address: Address, chain_id: u64) -> bool {
+        !self.is_expired() && self.address() == address && self.chain_id() == chain_id
+    }
+}
+
+fn iso_string(ms: f64) -> String {
+    Date::new(&JsValue::from_f64(ms)).to_iso_string().into()
+}
+
+/// 16 random bytes as hex. EIP-4361 needs at least 8 alphanumeric characters
+fn random_nonce() -> String {
+    let mut x = [0u8; 16];
+
+    web_sys::window()
+        .expect("no window")
+        .crypto()
+        .expect("no crypto")
+        .get_random_values_with_u8_array(&mut x)
+        .expect("failed to get random values");
+
+    hex::encode(x)
+}
+
+impl EIP1193Provider {
+    /// Ask the user to sign in with their first account on the wallet's current chain.
+    pub async fn sign_in_with_ethereum(
+        &self,
+        statement: Option<String>,
+    ) -> Result<SiweSession, SiweError> {
+        let address = *self
+            .request_accounts()
+            .await?
+            .first()
+            .ok_or(SiweError::NoAccount)?;
+
+        let chain_id = self.chain_id().await?;
+
+        let location = web_sys::window().expect("no window").location();
+
+        let now = Date::now();
+        let expires_at = now + SESSION_MS;
+
+        let message = SiweMessage {
+            domain: location.host().expect("no host"),
+            address,
+            statement,
+            uri: location.origin().expect("no origin"),
+            chain_id,
+            nonce: random_nonce(),
+            issued_at: iso_string(now),
+            expiration_time: Some(iso_string(expires_at)),
+        };
+
+        let data = format!("0x{}", hex::encode(message.to_string()));
+
+        let signature: String = self.request("personal_sign", &(data, address)).await?;
+
+        let signature: Signature = signature.parse()?;
+
+        // don't trust the wallet. make sure it actually signed with the account it said it did
+        message.verify(&signature)?;
+
+        Ok(SiweSession {
+            message,
+            signature,
+            expires_at: expires_at as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::signers::{LocalWallet, Signer};
+    use ethers::utils::hash_message;
+
+    fn message(address: Address) -> SiweMessage {
+        SiweMessage {
+            domain: "dice.example".to_string(),
+            address,
+            statement: Some("Sign in to Alderson Dice.".to_string()),
+            uri: "https://dice.example".to_string(),
+            chain_id: 42161,
+            nonce: "32891756".to_string(),
+            issued_at: "2024-06-01T16:25:24.000Z".to_string(),
+            expiration_time: Some("2024-06-02T16:25:24.000Z".to_string()),
+        }
+    }
+
+    #[test]
+    fn formats_message() {
+        let address = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"
+            .parse()
+            .unwrap();
+
+        let expected = "dice.example wants you to sign in with your Ethereum account:
+0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2
+
+Sign in to Alderson Dice.
+
+URI: https://dice.example
+Version: 1
+Chain ID: 42161
+Nonce: 32891756
+Issued At: 2024-06-01T16:25:24.000Z
+Expiration Time: 2024-06-02T16:25:24.000Z";
+
+        assert_eq!(message(address).to_string(), expected);
+    }
+
+    #[test]
+    fn verifies_signature() {
+        let wallet: LocalWallet =
+            "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+                .parse()
+                .unwrap();
+
+        let message = message(wallet.address());
+
+        let signature = wallet.sign_hash(hash_message(message.to_string())).unwrap();
+
+        message.verify(&signature).unwrap();
+
+        let other = SiweMessage {
+            address: Address::repeat_byte(1),
+            ..message
+        };
+
+        assert!(other.verify(&signature).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! A consistent view of the game at one block.
+//!
+//! Separate reads can land on different blocks, which would show a bag from one block next to pips from another.
+//! [`GameSnapshot::load`] does every read in one multicall that is pinned to a single block.
+use crate::bindings::game::{AllDiceCall, CurrentBagCall, DieInfo, RollCurrentBagCall};
+use crate::bindings::nft::TotalSupplyCall;
+use crate::multicall::{decode, Multicall};
+use crate::public_client::PublicClient;
+use crate::transport::{Transport, TransportError};
+use ethers::types::{Address, BlockId, U256};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    /// the block that everything was read at
+    pub block: BlockId,
+    /// every die that the nft contract has minted
+    pub total_dice: U256,
+    /// every color of die
+    pub dice: Vec<DieInfo>,
+    /// the ids of the dice in this block's bag
+    pub current_bag: Vec<U256>,
+    /// what each die in the bag rolled this block
+    pub current_pips: Vec<U256>,
+}
+
+impl GameSnapshot {
+    /// Read everything at `block`. Pinning to a block hash instead of a number keeps reorgs from mixing blocks.
+    pub async fn load<T: Transport>(
+        client: &PublicClient<T>,
+        nft: Address,
+        game: Address,
+        block: impl Into<BlockId>,
+    ) -> Result<Self, TransportError> {
+        let block = block.into();
+
+        let mut multicall = Multicall::new();
+
+        multicall
+            .add(nft, TotalSupplyCall)
+            .add(game, AllDiceCall)
+            .add(game, CurrentBagCall)
+            .add(game, RollCurrentBagCall);
+
+        let [total_dice, dice, current_bag, current_pips]: [_; 4] = multicall
+            .call(client, block)
+            .await?
+            .try_into()
+            .expect("multicall checks the number of results");
+
+        let (total_dice,): (U256,) = decode(total_dice)?;
+        let (dice,): ([DieInfo; 5],) = decode(dice)?;
+        let (current_bag,): (Vec<U256>,) = decode(current_bag)?;
+        let (current_pips,): (Vec<U256>,) = decode(current_pips)?;
+
+        Ok(Self {
+            block,
+            total_dice,
+            dice: dice.to_vec(),
+            current_bag,
+            current_pips,
+        })
+    }
+
+    /// The color of each die in the bag.
+    pub fn current_bag_colors(&self) -> Vec<DieInfo> {
+        self.current_bag
+            .iter()
+            .map(|x| {
+                let color_id = (x % self.dice.len()).as_usize();
+
+                self.dice[color_id].clone()
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use crate::eip1193::EIP1193Provider;
+    use crate::mock_provider::MockProvider;
+    use crate::multicall::{Aggregate3Call, Aggregate3Return};
+    use ethers::abi::{AbiDecode, AbiEncode};
+    use ethers::types::{Bytes, H256};
+    use serde_json::json;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn die(symbol: &str) -> DieInfo {
+        DieInfo {
+            pips: [1, 2, 3, 4, 5, 6],
+            name: symbol.to_string(),
+            symbol: symbol.to_string(),
+        }
+    }
+
+    fn ok(x: impl AbiEncode) -> (bool, Bytes) {
+        (true, x.encode().into())
+    }
+
+    #[wasm_bindgen_test]
+    async fn reads_everything_at_one_block() {
+        let dice = ["R", "G", "B", "Y", "P"].map(die);
+
+        let return_data = vec![
+            ok(U256::from(42)),
+            ok((dice.clone(),)),
+            ok((vec![U256::from(1), U256::from(7)],)),
+            ok((vec![U256::from(3), U256::from(6)],)),
+        ];
+
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+            "eth_call": Bytes::from(Aggregate3Return { return_data }.encode()),
+        }));
+
+        let client = PublicClient::new(EIP1193Provider::new(mock.inner()).unwrap());
+
+        let nft = Address::repeat_byte(0x11);
+        let game = Address::repeat_byte(0x22);
+        let hash = H256::repeat_byte(0xab);
+
+        let snapshot = GameSnapshot::load(&client, nft, game, hash).await.unwrap();
+
+        assert_eq!(snapshot.total_dice, 42.into());
+        assert_eq!(snapshot.current_pips, vec![3.into(), 6.into()]);
+        assert_eq!(
+            snapshot
+                .current_bag_colors()
+                .iter()
+                .map(|x| x.symbol.as_str())
+                .collect::<Vec<_>>(),
+            vec!["G", "B"]
+        );
+
+        // one request, pinned to the block hash
+        let requests = mock.requests_for("eth_call");
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0][1], json!({ "blockHash": hash }));
+
+        let data: Bytes = serde_json::from_value(requests[0][0]["data"].clone()).unwrap();
+
+        let calls = Aggregate3Call::decode(data).unwrap().calls;
+
+        assert_eq!(
+            calls.iter().map(|x| x.target).collect::<Vec<_>>(),
+            vec![nft, game, game, game]
+        );
+    }
+}
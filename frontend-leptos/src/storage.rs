@@ -0,0 +1,147 @@
+//! Things we remember between visits. Everything here is in the browser's localStorage.
+use crate::backfill::Checkpoint;
+use crate::eip6963::EIP6963ProviderDetail;
+use serde::{Deserialize, Serialize};
+use web_sys::Storage;
+
+const SAVED_WALLET_KEY: &str = "alderson-dice:wallet";
+
+fn rpc_urls_key(chain_id: u64) -> String {
+    format!("alderson-dice:rpc:{}", chain_id)
+}
+
+fn checkpoint_key(chain_id: u64, name: &str) -> String {
+    format!("alderson-dice:backfill:{}:{}", chain_id, name)
+}
+
+/// The wallet that the user last connected with.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SavedWallet {
+    pub rdns: String,
+    /// wallets are supposed to make a new uuid every page load, but some keep it. it is checked before rdns
+    pub uuid: String,
+    /// the chain the wallet was on. reconnecting switches back to it if the wallet moved
+    pub chain_id: u64,
+}
+
+impl SavedWallet {
+    pub fn new(detail: &EIP6963ProviderDetail, chain_id: u64) -> Self {
+        Self {
+            rdns: detail.rdns().to_string(),
+            uuid: detail.uuid().to_string(),
+            chain_id,
+        }
+    }
+
+    pub fn matches(&self, detail: &EIP6963ProviderDetail) -> bool {
+        self.uuid == detail.uuid() || (!self.rdns.is_empty() && self.rdns == detail.rdns())
+    }
+}
+
+/// None if storage is disabled. Private browsing in some browsers does that.
+fn local_storage() -> Option<Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+pub fn load_wallet() -> Option<SavedWallet> {
+    let x = local_storage()?.get_item(SAVED_WALLET_KEY).ok()??;
+
+    decode_wallet(&x)
+}
+
+pub fn save_wallet(wallet: &SavedWallet) {
+    let Some(storage) = local_storage() else {
+        return;
+    };
+
+    let x = encode_wallet(wallet);
+
+    if let Err(err) = storage.set_item(SAVED_WALLET_KEY, &x) {
+        leptos::logging::warn!("failed to save wallet: {:?}", err);
+    }
+}
+
+fn encode_wallet(wallet: &SavedWallet) -> String {
+    serde_json::to_string(wallet).expect("saved wallet is always json")
+}
+
+fn decode_wallet(x: &str) -> Option<SavedWallet> {
+    // a bad value is probably from an old version of the app. ignore it
+    serde_json::from_str(x).ok()
+}
+
+pub fn forget_wallet() {
+    if let Some(storage) = local_storage() {
+        let _ = storage.remove_item(SAVED_WALLET_KEY);
+    }
+}
+
+/// RPC urls that the user configured for a chain. See [`ChainConfig`](crate::chains::ChainConfig).
+pub fn load_rpc_urls(chain_id: u64) -> Option<Vec<String>> {
+    let x = local_storage()?.get_item(&rpc_urls_key(chain_id)).ok()??;
+
+    serde_json::from_str(&x).ok()
+}
+
+pub fn save_rpc_urls(chain_id: u64, urls: &[String]) {
+    let Some(storage) = local_storage() else {
+        return;
+    };
+
+    let x = serde_json::to_string(urls).expect("urls are always json");
+
+    if let Err(err) = storage.set_item(&rpc_urls_key(chain_id), &x) {
+        leptos::logging::warn!("failed to save rpc urls: {:?}", err);
+    }
+}
+
+pub fn forget_rpc_urls(chain_id: u64) {
+    if let Some(storage) = local_storage() {
+        let _ = storage.remove_item(&rpc_urls_key(chain_id));
+    }
+}
+
+/// How far the backfill called `name` got. See [`Backfill`](crate::backfill::Backfill).
+pub fn load_checkpoint(chain_id: u64, name: &str) -> Option<Checkpoint> {
+    let x = local_storage()?
+        .get_item(&checkpoint_key(chain_id, name))
+        .ok()??;
+
+    serde_json::from_str(&x).ok()
+}
+
+pub fn save_checkpoint(chain_id: u64, name: &str, checkpoint: &Checkpoint) {
+    let Some(storage) = local_storage() else {
+        return;
+    };
+
+    let x = serde_json::to_string(checkpoint).expect("checkpoints are always json");
+
+    if let Err(err) = storage.set_item(&checkpoint_key(chain_id, name), &x) {
+        leptos::logging::warn!("failed to save checkpoint: {:?}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_wallets_round_trip() {
+        let wallet = SavedWallet {
+            rdns: "io.metamask".to_string(),
+            uuid: "350670db-19fa-4704-a166-e52e178b59d2".to_string(),
+            chain_id: 0xa4b1,
+        };
+
+        assert_eq!(decode_wallet(&encode_wallet(&wallet)), Some(wallet));
+
+        // saved before the chain was
+        assert_eq!(
+            decode_wallet(
+                r#"{"rdns":"io.metamask","uuid":"350670db-19fa-4704-a166-e52e178b59d2"}"#
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! JSON-RPC transports that don't go through viem.
+//!
+//! [`HttpTransport`] uses reqwest, which is `fetch` in the browser and hyper everywhere else, so the reads built on top
+//! of [`Transport`] work for native tools too. [`EIP1193Provider`] is a transport for when the user's wallet should
+//! do the reads.
+use crate::eip1193::{EIP1193Provider, ProviderRpcError};
+use crate::fallback::FallbackTransport;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug)]
+pub enum TransportError {
+    /// the request never got a response
+    Http(reqwest::Error),
+    /// the node responded with an error
+    Rpc {
+        code: i64,
+        message: String,
+        data: Option<Value>,
+    },
+    Provider(ProviderRpcError),
+    InvalidRequest(String),
+    InvalidResponse(String),
+}
+
+impl TransportError {
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            Self::Rpc { code, .. } => Some(*code),
+            Self::Provider(x) => x.code().map(|x| x.code()),
+            _ => None,
+        }
+    }
+
+    /// True if a different node might answer. Errors like a revert would be the same everywhere.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Http(_) | Self::InvalidResponse(_) => true,
+            // internal error, limit exceeded, and rate limited
+            Self::Rpc { code, .. } => matches!(code, -32603 | -32005 | 429),
+            Self::Provider(_) | Self::InvalidRequest(_) => false,
+        }
+    }
+}
+
+impl std::fmt::Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Http(x) => write!(f, "http error: {}", x),
+            Self::Rpc { code, message, .. } => write!(f, "{}: {}", code, message),
+            Self::Provider(x) => write!(f, "{}", x),
+            Self::InvalidRequest(x) => write!(f, "invalid request: {}", x),
+            Self::InvalidResponse(x) => write!(f, "invalid response: {}", x),
+        }
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+impl From<reqwest::Error> for TransportError {
+    fn from(x: reqwest::Error) -> Self {
+        Self::Http(x)
+    }
+}
+
+impl From<ProviderRpcError> for TransportError {
+    fn from(x: ProviderRpcError) -> Self {
+        Self::Provider(x)
+    }
+}
+
+/// Something that can send a JSON-RPC request and return its result.
+///
+/// Futures aren't `Send` because nothing in the browser is.
+#[allow(async_fn_in_trait)]
+pub trait Transport {
+    /// `params` is usually a tuple or a slice since JSON-RPC params are positional. Use `&()` for methods that don't
+    /// take any params.
+    async fn request<P: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &P,
+    ) -> Result<R, TransportError>;
+}
+
+impl Transport for EIP1193Provider {
+    async fn request<P: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &P,
+    ) -> Result<R, TransportError> {
+        Ok(EIP1193Provider::request(self, method, params).await?)
+    }
+}
+
+/// The wallet while it is connected to our chain. Otherwise the configured RPCs.
+#[derive(Clone, Debug)]
+pub enum ReadTransport {
+    Rpc(Rc<FallbackTransport>),
+    Wallet(EIP1193Provider),
+}
+
+impl PartialEq for ReadTransport {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Rpc(a), Self::Rpc(b)) => Rc::ptr_eq(a, b),
+            (Self::Wallet(a), Self::Wallet(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Transport for ReadTransport {
+    async fn request<P: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &P,
+    ) -> Result<R, TransportError> {
+        match self {
+            Self::Rpc(x) => x.request(method, params).await,
+            Self::Wallet(x) => Transport::request(x, method, params).await,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonRpcRequest<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+    #[serde(default)]
+    data: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcResponse {
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<JsonRpcError>,
+}
+
+impl JsonRpcResponse {
+    fn into_result<R: DeserializeOwned>(self, method: &str) -> Result<R, TransportError> {
+        if let Some(x) = self.error {
+            return Err(TransportError::Rpc {
+                code: x.code,
+                message: x.message,
+                data: x.data,
+            });
+        }
+
+        // a missing result is the same as null. methods like eth_getBlockByNumber return that
+        serde_json::from_value(self.result.unwrap_or(Value::Null))
+            .map_err(|err| TransportError::InvalidResponse(format!("{}: {}", method, err)))
+    }
+}
+
+/// JSON-RPC over HTTP POST.
+#[derive(Debug)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    next_id: AtomicU64,
+}
+
+impl HttpTransport {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Transport for HttpTransport {
+    async fn request<P: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &P,
+    ) -> Result<R, TransportError> {
+        let params = match serde_json::to_value(params) {
+            Ok(Value::Null) => Value::Array(vec![]),
+            Ok(x) => x,
+            Err(err) => {
+                return Err(TransportError::InvalidRequest(format!(
+                    "{}: {}",
+                    method, err
+                )))
+            }
+        };
+
+        let body = JsonRpcRequest {
+            jsonrpc: "2.0",
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            method,
+            params,
+        };
+
+        let response: JsonRpcResponse = self
+            .client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        response.into_result(method)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::U64;
+    use serde_json::json;
+
+    fn response(x: Value) -> JsonRpcResponse {
+        serde_json::from_value(x).unwrap()
+    }
+
+    #[test]
+    fn decodes_results_and_errors() {
+        let x: U64 = response(json!({ "jsonrpc": "2.0", "id": 1, "result": "0x10" }))
+            .into_result("eth_blockNumber")
+            .unwrap();
+
+        assert_eq!(x, U64::from(16));
+
+        let x: Option<U64> = response(json!({ "jsonrpc": "2.0", "id": 2, "result": null }))
+            .into_result("eth_getBlockByNumber")
+            .unwrap();
+
+        assert_eq!(x, None);
+
+        let err = response(json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "error": { "code": -32005, "message": "query returned more than 10000 results" },
+        }))
+        .into_result::<U64>("eth_getLogs")
+        .unwrap_err();
+
+        assert_eq!(err.code(), Some(-32005));
+    }
+}
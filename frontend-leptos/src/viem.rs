@@ -2,14 +2,18 @@
 //! TODO: component for the viem client https://viem.sh/docs/clients/transports/custom + https://viem.sh/docs/clients/transports/fallback
 //! TODO: <https://docs.walletconnect.com/web3modal/javascript/about>? use their modal instead of building all of it ourselves?
 //! TODO: private wallet_client that sends to a protected relay instead of the user's node?
-use std::collections::HashMap;
-
-use js_sys::{Function, Object, Promise, Reflect};
-use leptos::logging::log;
-use leptos::WriteSignal;
+use js_sys::{BigInt, Function, Object, Promise, Reflect};
+use leptos::logging::warn;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::rc::Rc;
+use std::time::Duration;
 use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::future_to_promise;
 
 use crate::createPublicClientForChain;
+use crate::eip1193::{BlockHeader, ProviderRpcError};
+use crate::transport::{Transport, TransportError};
 
 use super::createWalletClientForChain;
 
@@ -24,10 +28,19 @@ pub struct ViemPublicClient {
 }
 
 impl ViemPublicClient {
-    pub fn new(chain_id: String, eip1193_provider: Option<JsValue>) -> Self {
+    /// Reads go to the wallet first if there is one. `rpc` is the fallback.
+    ///
+    /// viem goes through our own transport instead of its `fallback` over the same urls. That way there is only one
+    /// health ranking and the diagnostics panel shows all of the reads.
+    pub fn new<T: Transport + 'static>(
+        chain_id: String,
+        eip1193_provider: Option<JsValue>,
+        rpc: Rc<T>,
+    ) -> Self {
         let eip1193_provider = eip1193_provider.unwrap_or_else(JsValue::undefined);
 
-        let public_client = createPublicClientForChain(chain_id, eip1193_provider);
+        let public_client =
+            createPublicClientForChain(chain_id, eip1193_provider, transport_provider(rpc));
 
         Self {
             inner: public_client,
@@ -38,36 +51,22 @@ impl ViemPublicClient {
         self.inner.clone()
     }
 
-    /// TODO: return something that can be used to cancel the subscription
-    pub fn watch_heads(
+    /// viem's `watchBlocks`. Returns viem's `unwatch` function.
+    ///
+    /// Usually you want a [`BlockWatcher`](crate::block_watcher::BlockWatcher) instead of calling this directly.
+    pub fn watch_blocks(
         &self,
-        set_watch_heads: WriteSignal<Option<HashMap<String, JsValue>>>,
+        polling_interval: Duration,
         emit_missed: bool,
+        on_block: Rc<dyn Fn(BlockHeader)>,
     ) -> Function {
-        let inner = self.inner.clone();
-
-        let closure = Closure::wrap(Box::new(move |block_header: JsValue| {
-            let block_header = block_header
-                .dyn_into::<Object>()
-                .expect("header not an object");
-
-            let block_header_keys = Reflect::own_keys(&block_header).expect("has keys");
-
-            // TODO: i feel like there is a better way to turn an Object into a HashMap. probably using "entries"
-            let mut block_header_map = HashMap::new();
-
-            for key in block_header_keys.iter() {
-                let value = Reflect::get(&block_header, &key).expect("getting key");
-
-                let key = key.as_string().expect("key is not a string");
-
-                block_header_map.insert(key, value);
-            }
-
-            log!("new block header to {:?}: {:?}", inner, block_header_map);
-
-            set_watch_heads(Some(block_header_map));
-        }) as Box<dyn FnMut(JsValue)>);
+        let closure =
+            Closure::wrap(
+                Box::new(move |block: JsValue| match block_header_from_viem(&block) {
+                    Ok(x) => on_block(x),
+                    Err(err) => warn!("invalid block from viem: {:?}", err),
+                }) as Box<dyn FnMut(JsValue)>,
+            );
 
         let closure = closure.into_js_value();
 
@@ -78,10 +77,12 @@ impl ViemPublicClient {
         Reflect::set(&arguments, &"emitMissed".into(), &emit_missed.into())
             .expect("setting emitMissed");
         Reflect::set(&arguments, &"emitOnBegin".into(), &true.into()).expect("setting emitOnBegin");
-
-        // TODO: different polling interval depending on the chain blocktime
-        Reflect::set(&arguments, &"pollingInterval".into(), &500.into())
-            .expect("setting pollingInterval");
+        Reflect::set(
+            &arguments,
+            &"pollingInterval".into(),
+            &(polling_interval.as_millis() as f64).into(),
+        )
+        .expect("setting pollingInterval");
 
         let watch_blocks_fn = Reflect::get(&self.inner, &"watchBlocks".into())
             .expect("getting watchBlocks")
@@ -96,6 +97,90 @@ impl ViemPublicClient {
     }
 }
 
+/// viem formats blocks with bigints. Turn those back into hex so that the block parses like one from `newHeads`.
+fn block_header_from_viem(block: &JsValue) -> Result<BlockHeader, JsValue> {
+    let rpc_block = Object::new();
+
+    for key in Reflect::own_keys(block)?.iter() {
+        let value = Reflect::get(block, &key)?;
+
+        let value = match value.dyn_ref::<BigInt>() {
+            Some(x) => format!("0x{}", String::from(x.to_string(16)?)).into(),
+            None => value,
+        };
+
+        Reflect::set(&rpc_block, &key, &value)?;
+    }
+
+    Ok(serde_wasm_bindgen::from_value(rpc_block.into())?)
+}
+
+/// An EIP-1193 `request` object for viem's `custom` transport that sends everything through `transport`.
+fn transport_provider<T: Transport + 'static>(transport: Rc<T>) -> JsValue {
+    let request = Closure::wrap(Box::new(move |args: JsValue| {
+        let transport = transport.clone();
+
+        future_to_promise(async move {
+            let method = Reflect::get(&args, &"method".into())?
+                .as_string()
+                .ok_or_else(|| JsValue::from("method is not a string"))?;
+
+            let params: Value =
+                serde_wasm_bindgen::from_value(Reflect::get(&args, &"params".into())?)
+                    .unwrap_or(Value::Null);
+
+            let result: Value = transport
+                .request(&method, &params)
+                .await
+                .map_err(|err| transport_error_to_js(&err))?;
+
+            Ok(result.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+        })
+    }) as Box<dyn FnMut(JsValue) -> Promise>);
+
+    let provider = Object::new();
+
+    Reflect::set(&provider, &"request".into(), &request.into_js_value())
+        .expect("failed to set request");
+
+    provider.into()
+}
+
+/// viem turns anything with a numeric `code` into the matching RPC error. reverts need the `data` too
+fn transport_error_to_js(err: &TransportError) -> JsValue {
+    let (code, message, data) = match err {
+        TransportError::Rpc {
+            code,
+            message,
+            data,
+        } => (
+            *code,
+            message.clone(),
+            data.as_ref().and_then(|x| {
+                x.serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+                    .ok()
+            }),
+        ),
+        TransportError::Provider(ProviderRpcError::Rpc {
+            code,
+            message,
+            data,
+        }) => (code.code(), message.clone(), data.clone()),
+        err => return js_sys::Error::new(&err.to_string()).into(),
+    };
+
+    let x = Object::new();
+
+    Reflect::set(&x, &"code".into(), &JsValue::from(code as f64)).expect("failed to set code");
+    Reflect::set(&x, &"message".into(), &message.into()).expect("failed to set message");
+
+    if let Some(data) = data {
+        Reflect::set(&x, &"data".into(), &data).expect("failed to set data");
+    }
+
+    x.into()
+}
+
 impl ViemWalletClient {
     /// TODO: eventually this can
     pub fn new(chain_id: String, eip1193_provider: JsValue) -> Self {
@@ -135,307 +220,175 @@ impl ViemWalletClient {
 
         Ok(accounts)
     }
-}
 
-impl std::fmt::Debug for ViemPublicClient {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("ViemPublicClient").finish()
-    }
-}
+    /// Send a request through the client. viem clients have the same `request` as the provider they wrap.
+    pub async fn request<P: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &P,
+    ) -> Result<R, ProviderRpcError> {
+        let request = Reflect::get(&self.inner, &"request".into())?.dyn_into::<Function>()?;
 
-impl std::fmt::Debug for ViemWalletClient {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("ViemWalletClient").finish()
-    }
-}
+        let params = params
+            .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+            .map_err(|err| ProviderRpcError::InvalidRequest(format!("{}: {}", method, err)))?;
 
-/// TODO: use automatically generated bindings instead
-///
-/// In general, contract instance methods follow the following format:
-///
-/// // function
-/// contract.(estimateGas|read|simulate|write).(functionName)(args, options)
-///
-/// // event
-/// contract.(createEventFilter|getEvents|watchEvent).(eventName)(args, options)
-#[derive(Clone)]
-pub struct ReadOnlyContract {
-    pub inner: JsValue,
-
-    read_obj: Object,
-    create_event_filter_obj: Object,
-    estimate_gas_obj: Object,
-    get_events_obj: Object,
-    simulate_obj: Object,
-    watch_event_obj: Object,
-}
+        let arg1 = Object::new();
 
-impl PartialEq for ReadOnlyContract {
-    fn eq(&self, other: &Self) -> bool {
-        self.inner == other.inner
-    }
-}
+        Reflect::set(&arg1, &"method".into(), &method.into())?;
+        Reflect::set(&arg1, &"params".into(), &params)?;
 
-impl std::fmt::Debug for ReadOnlyContract {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("ReadOnlyContract").finish_non_exhaustive()
-    }
-}
+        let promise: Promise = request.call1(&self.inner, &arg1)?.unchecked_into();
 
-#[derive(Clone)]
-pub struct ReadAndWriteContract {
-    contract: ReadOnlyContract,
+        let result = wasm_bindgen_futures::JsFuture::from(promise).await?;
 
-    write_obj: Object,
+        serde_wasm_bindgen::from_value(result)
+            .map_err(|err| ProviderRpcError::InvalidResponse(format!("{}: {}", method, err)))
+    }
 }
 
-impl PartialEq for ReadAndWriteContract {
-    fn eq(&self, other: &Self) -> bool {
-        self.contract == other.contract
+impl std::fmt::Debug for ViemPublicClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ViemPublicClient").finish()
     }
 }
 
-impl std::fmt::Debug for ReadAndWriteContract {
+impl std::fmt::Debug for ViemWalletClient {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("ReadAndWriteContract")
-            .finish_non_exhaustive()
+        f.debug_struct("ViemWalletClient").finish()
     }
 }
 
-impl ReadOnlyContract {
-    pub fn new(inner: JsValue) -> Self {
-        // TODO: do javascript checks to see if it has the correct public client type
-
-        let read_obj = Reflect::get(&inner, &"read".into())
-            .expect("getting read")
-            .dyn_into::<Object>()
-            .expect("read is not an object");
-
-        let create_event_filter_obj = Reflect::get(&inner, &"createEventFilter".into())
-            .expect("getting createEventFilter")
-            .dyn_into::<Object>()
-            .expect("createEventFilter is not a function");
-
-        let estimate_gas_obj = Reflect::get(&inner, &"estimateGas".into())
-            .expect("getting estimateGas")
-            .dyn_into::<Object>()
-            .expect("estimateGas is not a function");
-
-        let get_events_obj = Reflect::get(&inner, &"getEvents".into())
-            .expect("getting getEvents")
-            .dyn_into::<Object>()
-            .expect("getEvents is not a function");
-
-        let simulate_obj = Reflect::get(&inner, &"simulate".into())
-            .expect("getting simulate")
-            .dyn_into::<Object>()
-            .expect("simulate is not a function");
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use crate::eip1193::EIP1193Provider;
+    use crate::fallback::FallbackTransport;
+    use crate::mock_provider::MockProvider;
+    use ethers::types::H256;
+    use js_sys::Array;
+    use serde_json::json;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    async fn call_async(client: &JsValue, fn_name: &str) -> JsValue {
+        let f = Reflect::get(client, &fn_name.into())
+            .unwrap()
+            .dyn_into::<Function>()
+            .unwrap();
 
-        let watch_event_obj = Reflect::get(&inner, &"watchEvent".into())
-            .expect("getting watchEvent")
-            .dyn_into::<Object>()
-            .expect("watchEvent is not a function");
+        let promise: Promise = f.call0(client).unwrap().unchecked_into();
 
-        Self {
-            inner,
-            read_obj,
-            create_event_filter_obj,
-            estimate_gas_obj,
-            get_events_obj,
-            simulate_obj,
-            watch_event_obj,
-        }
+        wasm_bindgen_futures::JsFuture::from(promise).await.unwrap()
     }
 
-    pub fn address(&self) -> String {
-        Reflect::get(&self.inner, &"address".into())
-            .expect("failed to get address")
-            .as_string()
-            .expect("contract should always have an address string")
-    }
+    #[wasm_bindgen_test]
+    async fn public_client_reads_through_the_wallet() {
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_blockNumber": "0x2a",
+        }));
 
-    async fn run(
-        &self,
-        obj: &Object,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        let f = Reflect::get(obj, &fn_name.into())
-            .expect("getting function from obj")
-            .dyn_into::<Function>()
-            .expect("fn_name is not a function");
+        let rpc = Rc::new(FallbackTransport::http(&[]));
 
-        let promise: Promise = f
-            .call2(&self.inner, args, options)?
-            .dyn_into::<Promise>()
-            .expect("not a promise");
+        let client = ViemPublicClient::new("0xa4b1".to_string(), Some(mock.inner()), rpc);
 
-        wasm_bindgen_futures::JsFuture::from(promise).await
-    }
+        let block_number = call_async(&client.inner(), "getBlockNumber").await;
 
-    pub async fn read(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        self.run(&self.read_obj, fn_name, args, options).await
+        assert_eq!(block_number, JsValue::from(BigInt::from(42u64)));
+        assert_eq!(mock.requests_for("eth_blockNumber").len(), 1);
     }
 
-    pub async fn create_event_filter(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        self.run(&self.create_event_filter_obj, fn_name, args, options)
-            .await
-    }
+    #[wasm_bindgen_test]
+    async fn rpc_requests_go_through_the_transport() {
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_accounts": [],
+            "eth_blockNumber": "0x2a",
+        }));
 
-    pub async fn estimate_gas(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        self.run(&self.estimate_gas_obj, fn_name, args, options)
-            .await
-    }
+        let rpc = Rc::new(FallbackTransport::new([(
+            "mock".to_string(),
+            EIP1193Provider::new(mock.inner()).unwrap(),
+        )]));
 
-    pub async fn get_events(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        self.run(&self.get_events_obj, fn_name, args, options).await
-    }
+        let provider = transport_provider(rpc.clone());
 
-    pub async fn simulate(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        self.run(&self.simulate_obj, fn_name, args, options).await
-    }
+        let request = |method: &str| {
+            let args = Object::new();
+            Reflect::set(&args, &"method".into(), &method.into()).unwrap();
+            Reflect::set(&args, &"params".into(), &Array::new()).unwrap();
 
-    pub async fn watch_event(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        self.run(&self.watch_event_obj, fn_name, args, options)
-            .await
-    }
-}
+            let f = Reflect::get(&provider, &"request".into())
+                .unwrap()
+                .dyn_into::<Function>()
+                .unwrap();
 
-impl ReadAndWriteContract {
-    pub fn new(inner: JsValue) -> Self {
-        // TODO: do javascript checks to see if it has the correct public client type
+            let promise: Promise = f.call1(&provider, &args).unwrap().unchecked_into();
 
-        let write_obj = Reflect::get(&inner, &"write".into())
-            .expect("getting write")
-            .dyn_into::<Object>()
-            .expect("write is not an object");
+            wasm_bindgen_futures::JsFuture::from(promise)
+        };
 
-        // both reader and writer have an estimate gas fn
-        let estimate_gas_obj = Reflect::get(&inner, &"estimateGas".into())
-            .expect("getting estimateGas")
-            .dyn_into::<Object>()
-            .expect("estimateGas is not a function");
+        assert_eq!(request("eth_blockNumber").await.unwrap(), "0x2a");
 
-        let mut inner = ReadOnlyContract::new(inner);
+        // errors keep their code so that viem can tell what went wrong
+        mock.reject_once("eth_call", 3, "execution reverted");
 
-        inner.estimate_gas_obj = estimate_gas_obj;
+        let err = request("eth_call").await.unwrap_err();
 
-        Self {
-            contract: inner,
-            write_obj,
-        }
-    }
+        assert_eq!(
+            Reflect::get(&err, &"code".into()).unwrap().as_f64(),
+            Some(3.0)
+        );
 
-    pub fn address(&self) -> String {
-        self.contract.address()
+        // the same rankings that the diagnostics panel shows
+        assert_eq!(rpc.status()[0].health.requests, 2);
     }
 
-    pub fn inner(&self) -> JsValue {
-        self.contract.inner.clone()
-    }
+    #[wasm_bindgen_test]
+    fn parses_viem_blocks() {
+        let block = Object::new();
 
-    pub fn inner_ref(&self) -> &JsValue {
-        &self.contract.inner
-    }
+        let set = |key: &str, value: JsValue| {
+            Reflect::set(&block, &key.into(), &value).unwrap();
+        };
 
-    pub async fn read(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        self.contract.read(fn_name, args, options).await
-    }
+        set("number", BigInt::from(16u64).into());
+        set("hash", format!("{:?}", H256::repeat_byte(0x11)).into());
+        set(
+            "parentHash",
+            format!("{:?}", H256::repeat_byte(0x22)).into(),
+        );
+        set("timestamp", BigInt::from(0x665b4c00u64).into());
+        set("miner", "0x0000000000000000000000000000000000000000".into());
+        set("gasLimit", BigInt::from(30_000_000u64).into());
+        set("gasUsed", BigInt::from(0u64).into());
+        set("baseFeePerGas", JsValue::NULL);
+        set("transactions", Array::new().into());
 
-    pub async fn create_event_filter(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        // TODO: how do we unsubscribe from this?
-        self.contract
-            .create_event_filter(fn_name, args, options)
-            .await
-    }
+        let x = block_header_from_viem(&block.into()).unwrap();
 
-    pub async fn estimate_gas(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        self.contract.estimate_gas(fn_name, args, options).await
+        assert_eq!(x.number.as_u64(), 16);
+        assert_eq!(x.hash, H256::repeat_byte(0x11));
+        assert_eq!(x.timestamp, 0x665b4c00u64.into());
+        assert_eq!(x.base_fee_per_gas, None);
     }
 
-    pub async fn get_events(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        // TODO: change ok return value into an array of event logs
-        self.contract.get_events(fn_name, args, options).await
-    }
+    #[wasm_bindgen_test]
+    async fn wallet_client_requests_addresses() {
+        let mock = MockProvider::from_fixtures(json!({
+            "eth_chainId": "0xa4b1",
+            "eth_requestAccounts": ["0x00000000000000000000000000000000000a11ce"],
+        }));
 
-    pub async fn simulate(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        self.contract.simulate(fn_name, args, options).await
-    }
+        let client = ViemWalletClient::new("0xa4b1".to_string(), mock.inner());
 
-    pub async fn watch_event(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        // TODO: change ok return value into an "unwatch" function
-        self.contract.watch_event(fn_name, args, options).await
-    }
+        let addresses = client.request_addresses().await.unwrap();
 
-    pub async fn write(
-        &self,
-        fn_name: &str,
-        args: &JsValue,
-        options: &JsValue,
-    ) -> Result<JsValue, JsValue> {
-        self.contract
-            .run(&self.write_obj, fn_name, args, options)
-            .await
+        assert_eq!(
+            addresses,
+            vec!["0x00000000000000000000000000000000000a11ce".to_string()]
+        );
     }
 }